@@ -0,0 +1,368 @@
+// derives for enums. Each variant stands for one hash or integer value, which
+// is either the whole param, or the 'tag' field of a struct param
+
+use crate::proc_macro::TokenStream;
+use proc_macro2::TokenStream as Tokens;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::token::{Comma, Eq};
+use syn::{Error, Expr, Fields, Ident, Lit, Result as SynResult, Variant};
+
+use crate::rename::LabelRules;
+use crate::{
+    check_borrow_fields, check_coerce_fields, crate_path, lit_hash, parse_fields, FieldData,
+    MainAttributes,
+};
+
+const INVALID_VARIANT_ATTR_NAME: &str =
+    "Invalid variant attribute. Accepted attribute names are 'hash', 'value' and 'other'";
+const INVALID_VARIANT_ATTR_COUNT: &str =
+    "Invalid variant attributes. Only use 'hash', 'value' or 'other' attribute in variant once";
+const MIXED_VALUES: &str = "Enum variants can't mix hash values and integer values";
+const MISSING_VALUE: &str =
+    "Variants of an enum with integer values need a 'value' attribute or a discriminant";
+const FIELDS_WITHOUT_TAG: &str =
+    "Variants with fields need a 'tag' attribute on the enum to select the variant";
+const INVALID_OTHER: &str = "The 'other' variant must have exactly one unnamed field";
+const DUPLICATE_OTHER: &str = "Only one variant can use the 'other' attribute";
+const REST_IN_VARIANT: &str = "The 'rest' attribute can't be used on fields of enum variants";
+
+enum VariantAttribute {
+    Hash(Lit),
+    Value(Box<Expr>),
+    Other,
+}
+
+impl Parse for VariantAttribute {
+    fn parse(input: ParseStream) -> SynResult<Self> {
+        let key: Ident = input.parse()?;
+        match key.to_string().as_ref() {
+            "hash" => {
+                let _eq: Eq = input.parse()?;
+                Ok(VariantAttribute::Hash(input.parse()?))
+            }
+            "value" => {
+                let _eq: Eq = input.parse()?;
+                Ok(VariantAttribute::Value(input.parse()?))
+            }
+            "other" => Ok(VariantAttribute::Other),
+            _ => Err(input.error(INVALID_VARIANT_ATTR_NAME)),
+        }
+    }
+}
+
+enum VariantShape<'a> {
+    Unit,
+    Named(Vec<FieldData<'a>>),
+}
+
+struct VariantData<'a> {
+    ident: &'a Ident,
+    // an expression of the enum's repr type
+    value: Tokens,
+    shape: VariantShape<'a>,
+}
+
+struct EnumData<'a> {
+    path: Tokens,
+    repr: Tokens,
+    tag: Option<Tokens>,
+    variants: Vec<VariantData<'a>>,
+    other: Option<&'a Ident>,
+}
+
+fn parse_enum<'a>(
+    attrs: &MainAttributes,
+    variants: &'a Punctuated<Variant, Comma>,
+) -> SynResult<EnumData<'a>> {
+    let path = crate_path(attrs);
+    let tag = attrs.tag.as_ref().map(|tag| lit_hash(&path, tag));
+
+    // (variant, explicit hash, explicit integer value, fields)
+    let mut values = Vec::with_capacity(variants.len());
+    let mut other = None;
+
+    for variant in variants {
+        let variant_attrs = variant
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("prc"))
+            .map(|attr| attr.parse_args())
+            .collect::<SynResult<Vec<VariantAttribute>>>()?;
+
+        if variant_attrs.len() > 1 {
+            return Err(Error::new_spanned(variant, INVALID_VARIANT_ATTR_COUNT));
+        }
+
+        let (hash, int) = match variant_attrs.into_iter().next() {
+            Some(VariantAttribute::Other) => {
+                if !matches!(&variant.fields, Fields::Unnamed(fields) if fields.unnamed.len() == 1)
+                {
+                    return Err(Error::new_spanned(variant, INVALID_OTHER));
+                }
+                if other.replace(&variant.ident).is_some() {
+                    return Err(Error::new_spanned(variant, DUPLICATE_OTHER));
+                }
+                continue;
+            }
+            Some(VariantAttribute::Hash(hash)) => (Some(lit_hash(&path, &hash)), None),
+            Some(VariantAttribute::Value(value)) => (None, Some(quote!(#value))),
+            None => (
+                None,
+                variant
+                    .discriminant
+                    .as_ref()
+                    .map(|(_, discriminant)| quote!(#discriminant)),
+            ),
+        };
+
+        let shape = match &variant.fields {
+            Fields::Unit => VariantShape::Unit,
+            Fields::Named(_) if tag.is_none() => {
+                return Err(Error::new_spanned(variant, FIELDS_WITHOUT_TAG))
+            }
+            Fields::Named(fields) => {
+                let fields = parse_fields(&path, &fields.named, &LabelRules::default())?;
+                if let Some(field) = fields.iter().find(|field| field.rest) {
+                    return Err(Error::new(field.ident.span(), REST_IN_VARIANT));
+                }
+                check_borrow_fields(&fields)?;
+                VariantShape::Named(fields)
+            }
+            Fields::Unnamed(_) => return Err(Error::new_spanned(variant, FIELDS_WITHOUT_TAG)),
+        };
+
+        values.push((variant, hash, int, shape));
+    }
+
+    // the enum uses integer values if it has a repr or any variant has one
+    let is_int = attrs.repr.is_some() || values.iter().any(|(_, _, int, _)| int.is_some());
+    let repr = match &attrs.repr {
+        Some(repr) => quote!(#repr),
+        None if is_int => quote!(i32),
+        None => quote!(#path::hash40::Hash40),
+    };
+
+    let variants = values
+        .into_iter()
+        .map(|(variant, hash, int, shape)| {
+            let value = match (hash, int) {
+                (Some(_), _) if is_int => return Err(Error::new_spanned(variant, MIXED_VALUES)),
+                (Some(hash), _) => hash,
+                (None, Some(int)) => int,
+                (None, None) if is_int => return Err(Error::new_spanned(variant, MISSING_VALUE)),
+                (None, None) => {
+                    let label = attrs.labels.variant_label(&variant.ident.to_string());
+                    quote!(#path::hash40::hash40(#label))
+                }
+            };
+            Ok(VariantData {
+                ident: &variant.ident,
+                value,
+                shape,
+            })
+        })
+        .collect::<SynResult<Vec<_>>>()?;
+
+    Ok(EnumData {
+        path,
+        repr,
+        tag,
+        variants,
+        other,
+    })
+}
+
+impl EnumData<'_> {
+    /// Returns the variant matching `__prc_value` from the function, using
+    /// `read_field` to create the fields of struct variants
+    fn match_value(&self, read_field: impl Fn(&FieldData) -> Tokens) -> Tokens {
+        let repr = &self.repr;
+        let checks = self.variants.iter().map(|variant| {
+            let ident = variant.ident;
+            let value = &variant.value;
+            let construct = match &variant.shape {
+                VariantShape::Unit => quote!(Self::#ident),
+                VariantShape::Named(fields) => {
+                    let names = fields.iter().map(|field| field.ident);
+                    let reads = fields.iter().map(&read_field);
+                    quote!(Self::#ident { #( #names: #reads, )* })
+                }
+            };
+            quote! {
+                let __prc_expected: #repr = #value;
+                if __prc_value == __prc_expected {
+                    return Ok(#construct);
+                }
+            }
+        });
+        quote!(#( { #checks } )*)
+    }
+
+    /// The error for a value which doesn't match any variant, or the 'other'
+    /// variant if there is one. `new_error` creates the error from its kind
+    fn fallback(&self, new_error: impl Fn(Tokens) -> Tokens) -> Tokens {
+        let path = &self.path;
+        match self.other {
+            Some(other) => quote!(Ok(Self::#other(__prc_value))),
+            None => {
+                let tag_path = self.tag.as_ref().map(|tag| {
+                    quote!(__prc_error.path.push(#path::prc_trait::ErrorPathPart::Hash(#tag));)
+                });
+                let error = new_error(quote! {
+                    #path::prc_trait::PrcWrite::to_param(&__prc_value).map_or_else(
+                        #path::prc_trait::ErrorKind::Io,
+                        #path::prc_trait::ErrorKind::UnknownValue,
+                    )
+                });
+                quote! {
+                    #[allow(unused_mut)]
+                    let mut __prc_error = #error;
+                    #tag_path
+                    Err(__prc_error)
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn derive_enum(
+    ident: Ident,
+    attrs: &MainAttributes,
+    variants: &Punctuated<Variant, Comma>,
+) -> SynResult<TokenStream> {
+    let data = parse_enum(attrs, variants)?;
+    let path = &data.path;
+    let repr = &data.repr;
+
+    let (param_number, read_param) = match &data.tag {
+        Some(tag) => {
+            let checks = data.match_value(|field| field.read(path, &quote!(__prc_data)));
+            let fallback = data.fallback(|kind| {
+                quote! {
+                    #path::prc_trait::Error::new_with_pos(
+                        #kind,
+                        #path::prc_trait::ParamSource::position(reader, __prc_data.node),
+                    )
+                }
+            });
+            (
+                quote!(::std::option::Option::Some(#path::prc_trait::ParamNumber::Struct)),
+                quote! {
+                    let __prc_data = #path::prc_trait::StructData::from_stream(reader)?;
+                    let __prc_value: #repr = #path::prc_trait::Prc::read_from_struct(reader, #tag, ctx, __prc_data)?;
+                    #checks
+                    #fallback
+                },
+            )
+        }
+        None => {
+            // untagged variants have no fields to read
+            let checks = data.match_value(|_| unreachable!());
+            let fallback = data.fallback(|kind| {
+                quote! {
+                    #path::prc_trait::Error::new_with_pos(
+                        #kind,
+                        #path::prc_trait::ParamSource::position(reader, __prc_node),
+                    )
+                }
+            });
+            (
+                quote!(<#repr as #path::prc_trait::Prc>::param_number()),
+                quote! {
+                    let __prc_node = #path::prc_trait::ParamSource::node(reader)?;
+                    let __prc_value: #repr = #path::prc_trait::Prc::read_param(reader, ctx)?;
+                    #checks
+                    #fallback
+                },
+            )
+        }
+    };
+
+    Ok(quote! {
+        impl #path::prc_trait::Prc for #ident {
+            fn read_param<__S: #path::prc_trait::ParamSource>(reader: &mut __S, ctx: &#path::prc_trait::ReadContext) -> #path::prc_trait::Result<Self> {
+                #read_param
+            }
+
+            fn param_number() -> ::std::option::Option<#path::prc_trait::ParamNumber> {
+                #param_number
+            }
+        }
+    }
+    .into())
+}
+
+pub(crate) fn derive_write_enum(
+    ident: Ident,
+    attrs: &MainAttributes,
+    variants: &Punctuated<Variant, Comma>,
+) -> SynResult<TokenStream> {
+    let data = parse_enum(attrs, variants)?;
+    for variant in &data.variants {
+        if let VariantShape::Named(fields) = &variant.shape {
+            check_coerce_fields(fields)?;
+        }
+    }
+    let path = &data.path;
+    let repr = &data.repr;
+
+    let arms = data.variants.iter().map(|variant| {
+        let variant_ident = variant.ident;
+        let value = &variant.value;
+        let (pattern, fields) = match &variant.shape {
+            VariantShape::Unit => (quote!(Self::#variant_ident), vec![]),
+            VariantShape::Named(fields) => {
+                let names = fields.iter().map(|field| field.ident);
+                (quote!(Self::#variant_ident { #(#names),* }), fields.iter().collect())
+            }
+        };
+        let to_param = match &data.tag {
+            Some(tag) => {
+                let len = fields.len() + 1;
+                let writes = fields.iter().map(|field| {
+                    let name = field.ident;
+                    field.write(path, &quote!(#name), &quote!(&mut __prc_struct))
+                });
+                quote! {
+                    let mut __prc_struct = #path::ParamStruct(::std::vec::Vec::with_capacity(#len));
+                    #path::prc_trait::PrcWrite::write_to_struct(&__prc_value, #tag, &mut __prc_struct)?;
+                    #( #writes )*
+                    ::std::result::Result::Ok(#path::ParamKind::Struct(__prc_struct))
+                }
+            }
+            None => quote!(#path::prc_trait::PrcWrite::to_param(&__prc_value)),
+        };
+        quote! {
+            #pattern => {
+                let __prc_value: #repr = #value;
+                #to_param
+            }
+        }
+    });
+
+    let other_arm = data.other.map(|other| {
+        let to_param = match &data.tag {
+            Some(tag) => quote! {
+                let mut __prc_struct = #path::ParamStruct(::std::vec::Vec::with_capacity(1));
+                #path::prc_trait::PrcWrite::write_to_struct(__prc_value, #tag, &mut __prc_struct)?;
+                ::std::result::Result::Ok(#path::ParamKind::Struct(__prc_struct))
+            },
+            None => quote!(#path::prc_trait::PrcWrite::to_param(__prc_value)),
+        };
+        quote!(Self::#other(__prc_value) => { #to_param })
+    });
+
+    Ok(quote! {
+        impl #path::prc_trait::PrcWrite for #ident {
+            fn to_param(&self) -> ::std::result::Result<#path::ParamKind, ::std::io::Error> {
+                match self {
+                    #( #arms )*
+                    #other_arm
+                }
+            }
+        }
+    }
+    .into())
+}
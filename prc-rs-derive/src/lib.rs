@@ -0,0 +1,1235 @@
+extern crate proc_macro;
+use crate::proc_macro::TokenStream;
+use enums::{derive_enum, derive_write_enum};
+use quote::{format_ident, quote, quote_spanned};
+use rename::{LabelRules, RenameRule};
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+use syn::token::Eq;
+
+use syn::Lit;
+use syn::Path;
+use syn::{
+    Attribute, Data, DeriveInput, Expr, Field, Fields, GenericParam, Ident, LitStr, Member,
+    Result as SynResult, Type, Variant,
+};
+
+mod enums;
+mod rename;
+
+const NAMED_STRUCT_ONLY_ERR: &str = "Derive macro only implemented for named structs and enums";
+
+const INVALID_ATTR_NAME: &str =
+    "Invalid struct attribute. Accepted names are 'path', 'tag', 'repr', 'deny_unknown_fields', 'transparent', 'rename_all' and 'prefix'";
+const INVALID_ATTR_COUNT: &str =
+    "Invalid struct attributes. Only use each attribute in struct once";
+const ENUM_ONLY_ATTR: &str = "The 'tag' and 'repr' attributes can only be used on enums";
+const STRUCT_ONLY_ATTR: &str =
+    "The 'deny_unknown_fields' and 'transparent' attributes can only be used on structs";
+const INVALID_TRANSPARENT: &str =
+    "The 'transparent' attribute requires a struct with exactly one field, and no other attributes";
+
+const INVALID_FIELD_ATTR_NAME: &str =
+    "Invalid field attribute. Accepted attribute names are 'name', 'hash', 'alias', 'default', 'coerce', 'rest', 'with', 'from', 'try_from', 'into', 'key', 'range', 'len', 'validate', 'borrow', 'since', 'until' and 'if'";
+const INVALID_FIELD_ATTR_COUNT: &str =
+    "Invalid field attributes. Only use 'name' or 'hash' attribute in field once";
+const DUPLICATE_FIELD_ATTR: &str =
+    "Invalid field attributes. Only use 'default', 'coerce', 'range', 'len', 'into', 'validate', 'since', 'until' or 'if' attribute in field once";
+const INVALID_REST_FIELD: &str =
+    "The 'rest' attribute can't be combined with other field attributes";
+const INVALID_CONVERSION: &str =
+    "Invalid field attributes. Only use one of 'with', 'from', 'try_from', 'key', 'coerce' or 'borrow' attribute in field";
+const DUPLICATE_REST_FIELD: &str = "Only one field can use the 'rest' attribute";
+const DENY_UNKNOWN_WITH_REST: &str =
+    "The 'deny_unknown_fields' attribute can't be used with a 'rest' field";
+const BORROW_WITHOUT_DERIVE: &str =
+    "The 'borrow' attribute can only be used when deriving PrcBorrow";
+const INVALID_INTO: &str =
+    "The 'into' attribute can only be used with a 'from' or 'try_from' attribute";
+const CONVERSION_WITHOUT_INTO: &str =
+    "The 'from' and 'try_from' attributes need an 'into' attribute when deriving PrcWrite, giving the type the field is converted into to be written";
+const COERCE_WITH_WRITE: &str =
+    "The 'coerce' attribute can't be used when deriving PrcWrite, since the param would be written with the field's type instead of its original one";
+const INVALID_BORROW_STRUCT: &str =
+    "PrcBorrow can only be derived for named structs with at most one lifetime parameter and no other generics";
+
+#[proc_macro_derive(Prc, attributes(prc))]
+pub fn prc_derive(input: TokenStream) -> TokenStream {
+    match derive_or_error(input, derive_named_struct, derive_transparent, derive_enum) {
+        Err(err) => err.to_compile_error().into(),
+        Ok(result) => result,
+    }
+}
+
+#[proc_macro_derive(PrcBorrow, attributes(prc))]
+pub fn prc_borrow_derive(input: TokenStream) -> TokenStream {
+    match derive_borrow(input) {
+        Err(err) => err.to_compile_error().into(),
+        Ok(result) => result,
+    }
+}
+
+#[proc_macro_derive(PrcWrite, attributes(prc))]
+pub fn prc_write_derive(input: TokenStream) -> TokenStream {
+    match derive_or_error(
+        input,
+        derive_write_named_struct,
+        derive_write_transparent,
+        derive_write_enum,
+    ) {
+        Err(err) => err.to_compile_error().into(),
+        Ok(result) => result,
+    }
+}
+
+type NamedStructDerive =
+    fn(Ident, &MainAttributes, &Punctuated<Field, Comma>) -> SynResult<TokenStream>;
+type TransparentDerive = fn(Ident, &MainAttributes, &Field) -> SynResult<TokenStream>;
+type EnumDerive = fn(Ident, &MainAttributes, &Punctuated<Variant, Comma>) -> SynResult<TokenStream>;
+
+fn derive_or_error(
+    input: TokenStream,
+    derive: NamedStructDerive,
+    derive_transparent: TransparentDerive,
+    derive_enum: EnumDerive,
+) -> SynResult<TokenStream> {
+    let input: DeriveInput = syn::parse(input)?;
+    let ident = input.ident;
+
+    let attrs = parse_struct_attributes(&input.attrs)?;
+
+    match input.data {
+        Data::Struct(_) if attrs.tag.is_some() || attrs.repr.is_some() => {
+            Err(syn::Error::new(ident.span(), ENUM_ONLY_ATTR))
+        }
+        Data::Enum(_) if attrs.deny_unknown_fields || attrs.transparent => {
+            Err(syn::Error::new(ident.span(), STRUCT_ONLY_ATTR))
+        }
+        Data::Enum(data_enum) => derive_enum(ident, &attrs, &data_enum.variants),
+        Data::Struct(data_struct) if attrs.transparent => {
+            let mut fields = data_struct.fields.iter();
+            match (fields.next(), fields.next()) {
+                (Some(field), None)
+                    if !attrs.deny_unknown_fields
+                        && !field.attrs.iter().any(|attr| attr.path.is_ident("prc")) =>
+                {
+                    derive_transparent(ident, &attrs, field)
+                }
+                _ => Err(syn::Error::new(ident.span(), INVALID_TRANSPARENT)),
+            }
+        }
+        Data::Struct(data_struct) => match &data_struct.fields {
+            Fields::Named(fields) => derive(ident, &attrs, &fields.named),
+            Fields::Unnamed(..) | Fields::Unit => {
+                Err(syn::Error::new(ident.span(), NAMED_STRUCT_ONLY_ERR))
+            }
+        },
+        _ => Err(syn::Error::new(ident.span(), NAMED_STRUCT_ONLY_ERR)),
+    }
+}
+
+#[derive(Default)]
+struct MainAttributes {
+    path: Option<Path>,
+    // the field which selects the variant of an enum with struct variants
+    tag: Option<Lit>,
+    // the integer type read for enums with integer values
+    repr: Option<Type>,
+    // whether params which don't match a field are an error
+    deny_unknown_fields: bool,
+    // whether the struct is read and written as its only field
+    transparent: bool,
+    // the labels of fields, or of variants for enums, without a name or hash
+    labels: LabelRules,
+}
+
+enum MainAttribute {
+    Path(Path),
+    Tag(Lit),
+    Repr(Box<Type>),
+    DenyUnknownFields,
+    Transparent,
+    RenameAll(RenameRule),
+    Prefix(LitStr),
+}
+
+enum FieldAttribute {
+    Name(Lit),
+    Hash(Lit),
+    // another name for the param, tried in order after the field's own
+    Alias(Lit),
+    // the default value, or None for Default::default()
+    Default(Option<Box<Expr>>),
+    Coerce,
+    // collects the params which don't match any other field
+    Rest,
+    With(Path),
+    From(Box<Type>),
+    TryFrom(Box<Type>),
+    // the type a 'from' or 'try_from' field is converted into when written
+    Into(Box<Type>),
+    // the child of each struct in a list which the map is indexed by
+    Key(Lit),
+    // checks run on the value after it's read
+    Range(Box<Expr>),
+    Len(Box<Expr>),
+    Validate(Path),
+    // read with PrcBorrow, borrowing from the file
+    Borrow,
+    // the versions of the game which have the field
+    Since(LitStr),
+    Until(LitStr),
+    If(Path),
+}
+
+fn parse_struct_attributes(attrs: &[Attribute]) -> SynResult<MainAttributes> {
+    let mut attributes = MainAttributes::default();
+
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("prc"))
+        .try_for_each(|attr| {
+            let attr_kind: MainAttribute = attr.parse_args()?;
+            let already_set = match attr_kind {
+                MainAttribute::Path(path) => attributes.path.replace(path).is_some(),
+                MainAttribute::Tag(tag) => attributes.tag.replace(tag).is_some(),
+                MainAttribute::Repr(repr) => attributes.repr.replace(*repr).is_some(),
+                MainAttribute::DenyUnknownFields => {
+                    std::mem::replace(&mut attributes.deny_unknown_fields, true)
+                }
+                MainAttribute::Transparent => std::mem::replace(&mut attributes.transparent, true),
+                MainAttribute::RenameAll(rule) => {
+                    attributes.labels.rename_all.replace(rule).is_some()
+                }
+                MainAttribute::Prefix(prefix) => {
+                    attributes.labels.prefix.replace(prefix.value()).is_some()
+                }
+            };
+            if already_set {
+                return Err(syn::Error::new_spanned(attr, INVALID_ATTR_COUNT));
+            }
+
+            SynResult::Ok(())
+        })?;
+
+    Ok(attributes)
+}
+
+impl Parse for MainAttribute {
+    fn parse(input: ParseStream) -> SynResult<Self> {
+        let key: Ident = input.parse()?;
+        let struct_attr = match key.to_string().as_ref() {
+            "path" => {
+                let _eq: Eq = input.parse()?;
+                MainAttribute::Path(input.parse()?)
+            }
+            "tag" => {
+                let _eq: Eq = input.parse()?;
+                MainAttribute::Tag(input.parse()?)
+            }
+            "repr" => {
+                let _eq: Eq = input.parse()?;
+                MainAttribute::Repr(input.parse()?)
+            }
+            "deny_unknown_fields" => MainAttribute::DenyUnknownFields,
+            "transparent" => MainAttribute::Transparent,
+            "rename_all" => {
+                let _eq: Eq = input.parse()?;
+                MainAttribute::RenameAll(RenameRule::from_lit(&input.parse()?)?)
+            }
+            "prefix" => {
+                let _eq: Eq = input.parse()?;
+                MainAttribute::Prefix(input.parse()?)
+            }
+            _ => return Err(syn::Error::new(key.span(), INVALID_ATTR_NAME)),
+        };
+
+        SynResult::Ok(struct_attr)
+    }
+}
+
+impl Parse for FieldAttribute {
+    fn parse(input: ParseStream) -> SynResult<Self> {
+        // 'if' is a keyword, so it isn't parsed as an identifier by default
+        let key = input.call(Ident::parse_any)?;
+        match key.to_string().as_ref() {
+            "name" => {
+                let _eq: Eq = input.parse()?;
+                Ok(FieldAttribute::Name(input.parse()?))
+            }
+            "hash" => {
+                let _eq: Eq = input.parse()?;
+                Ok(FieldAttribute::Hash(input.parse()?))
+            }
+            "alias" => {
+                let _eq: Eq = input.parse()?;
+                Ok(FieldAttribute::Alias(input.parse()?))
+            }
+            "default" => {
+                if input.peek(Eq) {
+                    let _eq: Eq = input.parse()?;
+                    Ok(FieldAttribute::Default(Some(input.parse()?)))
+                } else {
+                    Ok(FieldAttribute::Default(None))
+                }
+            }
+            "coerce" => Ok(FieldAttribute::Coerce),
+            "borrow" => Ok(FieldAttribute::Borrow),
+            "since" => {
+                let _eq: Eq = input.parse()?;
+                Ok(FieldAttribute::Since(input.parse()?))
+            }
+            "until" => {
+                let _eq: Eq = input.parse()?;
+                Ok(FieldAttribute::Until(input.parse()?))
+            }
+            "if" => {
+                let _eq: Eq = input.parse()?;
+                let predicate: LitStr = input.parse()?;
+                Ok(FieldAttribute::If(predicate.parse()?))
+            }
+            "rest" | "flatten" => Ok(FieldAttribute::Rest),
+            "with" => {
+                let _eq: Eq = input.parse()?;
+                let module: LitStr = input.parse()?;
+                Ok(FieldAttribute::With(module.parse()?))
+            }
+            "from" => {
+                let _eq: Eq = input.parse()?;
+                let ty: LitStr = input.parse()?;
+                Ok(FieldAttribute::From(ty.parse()?))
+            }
+            "try_from" => {
+                let _eq: Eq = input.parse()?;
+                let ty: LitStr = input.parse()?;
+                Ok(FieldAttribute::TryFrom(ty.parse()?))
+            }
+            "into" => {
+                let _eq: Eq = input.parse()?;
+                let ty: LitStr = input.parse()?;
+                Ok(FieldAttribute::Into(ty.parse()?))
+            }
+            "key" => {
+                let _eq: Eq = input.parse()?;
+                Ok(FieldAttribute::Key(input.parse()?))
+            }
+            "range" => {
+                let _eq: Eq = input.parse()?;
+                Ok(FieldAttribute::Range(input.parse()?))
+            }
+            "len" => {
+                let _eq: Eq = input.parse()?;
+                Ok(FieldAttribute::Len(input.parse()?))
+            }
+            "validate" => {
+                let _eq: Eq = input.parse()?;
+                let function: LitStr = input.parse()?;
+                Ok(FieldAttribute::Validate(function.parse()?))
+            }
+            _ => Err(input.error(INVALID_FIELD_ATTR_NAME)),
+        }
+    }
+}
+
+fn crate_path(attrs: &MainAttributes) -> proc_macro2::TokenStream {
+    attrs
+        .path
+        .as_ref()
+        .map(|some_path| quote!(#some_path))
+        .unwrap_or(quote!(::prc))
+}
+
+/// The tokens for a hash given by a string label or its integer value
+fn lit_hash(path: &proc_macro2::TokenStream, lit: &Lit) -> proc_macro2::TokenStream {
+    match lit {
+        Lit::Str(label) => quote!(#path::hash40::hash40(#label)),
+        _ => quote!(#path::hash40::Hash40(#lit)),
+    }
+}
+
+/// The tokens for the version given by a string like "13.0.1"
+fn lit_version(path: &proc_macro2::TokenStream, lit: &LitStr) -> proc_macro2::TokenStream {
+    // parsed in a constant, so an invalid version fails the build at the attribute
+    quote_spanned! {lit.span()=> ({
+        const __PRC_VERSION: #path::prc_trait::Version =
+            #path::prc_trait::Version::from_attribute(#lit);
+        __PRC_VERSION
+    })}
+}
+
+/// How a field is converted from the param, when it isn't read with [Prc]
+enum Conversion {
+    // a module with read_param and to_param functions
+    With(Path),
+    // the type read with Prc, then converted to the field with TryFrom.
+    // This covers 'from' too, since every From impl provides TryFrom
+    From(Box<Type>),
+    // a list of structs, read into a map indexed by the child with this hash
+    Key(proc_macro2::TokenStream),
+}
+
+impl Conversion {
+    /// The function reading the field, like [Prc::read_param]
+    fn read_fn(&self, path: &proc_macro2::TokenStream, ty: &Type) -> proc_macro2::TokenStream {
+        match self {
+            Conversion::With(module) => quote!(#module::read_param),
+            Conversion::From(from) => quote!(#path::prc_trait::read_try_from::<_, #from, #ty>),
+            Conversion::Key(key) => {
+                quote!(|reader, ctx| #path::prc_trait::read_keyed_list(reader, ctx, #key))
+            }
+        }
+    }
+}
+
+/// A field of a struct, along with what its attributes change
+struct FieldData<'a> {
+    ident: &'a Ident,
+    ty: &'a Type,
+    hash: proc_macro2::TokenStream,
+    // other hashes the param is read from, when the field's hash is missing
+    aliases: Vec<proc_macro2::TokenStream>,
+    // the value used when the param is missing
+    default: Option<proc_macro2::TokenStream>,
+    coerce: bool,
+    // whether the field is read with PrcBorrow
+    borrow: bool,
+    // whether the field is a ParamStruct of the params no other field reads
+    rest: bool,
+    conversion: Option<Conversion>,
+    // the type a converted field is written as
+    into: Option<Box<Type>>,
+    range: Option<Box<Expr>>,
+    len: Option<Box<Expr>>,
+    validate: Option<Path>,
+    // the first version with the field, the first version without it, and
+    // a function deciding whether a version has it
+    since: Option<proc_macro2::TokenStream>,
+    until: Option<proc_macro2::TokenStream>,
+    predicate: Option<Path>,
+}
+
+impl FieldData<'_> {
+    /// The tokens reading the field from `struct_data`, using `reader` and `ctx`
+    fn read(
+        &self,
+        path: &proc_macro2::TokenStream,
+        struct_data: &proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let hash = self.read_hash();
+        let ty = self.ty;
+        let read = if let Some(conversion) = &self.conversion {
+            let read = conversion.read_fn(path, ty);
+            quote!(#struct_data.read_child_with(reader, #hash, ctx, #read))
+        } else if self.coerce {
+            quote!(#path::prc_trait::Coercible::read_from_struct_coerced(reader, #hash, ctx, #struct_data))
+        } else if self.borrow {
+            quote!(#path::prc_trait::PrcBorrow::borrow_from_struct(reader, #hash, ctx, #struct_data))
+        } else {
+            quote!(#path::prc_trait::Prc::read_from_struct(reader, #hash, ctx, #struct_data))
+        };
+        let read = self.or_default(path, read);
+        let read = match self.checks(path, &quote!(&__prc_checked)) {
+            Some(checks) => quote! {{
+                let __prc_checked: #ty = #read;
+                if let Err(kind) = #checks {
+                    return Err(#path::prc_trait::invalid_child(reader, #hash, ctx, #struct_data, kind));
+                }
+                __prc_checked
+            }},
+            None => read,
+        };
+        let hashes = self.hashes();
+        self.with_hash(quote!(#struct_data.find_hash(reader, &#hashes, ctx)), read)
+    }
+
+    /// The tokens for the hash the field is read from. For a field with
+    /// aliases, this is `__prc_hash`, bound by [FieldData::with_hash].
+    fn read_hash(&self) -> proc_macro2::TokenStream {
+        if self.aliases.is_empty() {
+            self.hash.clone()
+        } else {
+            quote!(__prc_hash)
+        }
+    }
+
+    /// The tokens for an array of the field's hash and its aliases, in order
+    fn hashes(&self) -> proc_macro2::TokenStream {
+        let hash = &self.hash;
+        let aliases = &self.aliases;
+        quote!([#hash, #(#aliases),*])
+    }
+
+    /// Binds `__prc_hash` to the result of `find` around `read`, if the
+    /// field has aliases
+    fn with_hash(
+        &self,
+        find: proc_macro2::TokenStream,
+        read: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        if self.aliases.is_empty() {
+            read
+        } else {
+            quote!({
+                let __prc_hash = #find;
+                #read
+            })
+        }
+    }
+
+    /// The tokens checking the field's validation attributes on `value`, a
+    /// reference to the field, or [None] if it has none. The checks evaluate
+    /// to a Result with the ErrorKind of the first one which failed.
+    fn checks(
+        &self,
+        path: &proc_macro2::TokenStream,
+        value: &proc_macro2::TokenStream,
+    ) -> Option<proc_macro2::TokenStream> {
+        let range = self
+            .range
+            .as_ref()
+            .map(|range| quote!(#path::prc_trait::check_range(#value, #range)));
+        let len = self
+            .len
+            .as_ref()
+            .map(|len| quote!(#path::prc_trait::check_len((#value).len(), #len)));
+        let validate = self
+            .validate
+            .as_ref()
+            .map(|function| quote!(#path::prc_trait::check_valid(#function(#value))));
+        let mut checks = range.into_iter().chain(len).chain(validate);
+        let first = checks.next()?;
+        Some(quote!(#first #(.and_then(|()| #checks))*))
+    }
+
+    /// The tokens unwrapping the field's `result`, handling a missing param
+    /// for the game version being read
+    fn or_default(
+        &self,
+        path: &proc_macro2::TokenStream,
+        result: proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let hash = self.read_hash();
+        let result = match &self.default {
+            Some(default) => {
+                quote!(#path::prc_trait::default_if_missing(#result, #hash, || #default))
+            }
+            None => result,
+        };
+        let condition = match self.version_condition(path) {
+            Some(condition) => condition,
+            None => return quote!(#result?),
+        };
+        let default = self.default_value();
+        quote! {
+            match ctx.version() {
+                Some(__prc_version) if !(#condition) => #default,
+                __prc_version => #path::prc_trait::require_for_version(#result, #hash, __prc_version, || #default)?,
+            }
+        }
+    }
+
+    /// The tokens deciding whether `__prc_version` has the field, or [None]
+    /// if every version has it
+    fn version_condition(
+        &self,
+        path: &proc_macro2::TokenStream,
+    ) -> Option<proc_macro2::TokenStream> {
+        let since = self
+            .since
+            .as_ref()
+            .map(|since| quote!(#since <= __prc_version));
+        let until = self
+            .until
+            .as_ref()
+            .map(|until| quote!(__prc_version < #until));
+        let predicate = self
+            .predicate
+            .as_ref()
+            .map(|predicate| quote!(#predicate(__prc_version)));
+        let mut conditions = since.into_iter().chain(until).chain(predicate);
+        let first = conditions.next()?;
+        // the type is named, in case the field only has a predicate
+        Some(quote!({
+            let __prc_version: #path::prc_trait::Version = __prc_version;
+            #first #(&& #conditions)*
+        }))
+    }
+
+    /// The value of the field when its param isn't read
+    fn default_value(&self) -> proc_macro2::TokenStream {
+        match &self.default {
+            Some(default) => default.clone(),
+            None => quote!(::std::default::Default::default()),
+        }
+    }
+
+    /// The tokens reading the field in diagnostic mode, recording errors in
+    /// `errors` and returning an Option
+    fn read_diagnostic(
+        &self,
+        path: &proc_macro2::TokenStream,
+        struct_data: &proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let hash = self.read_hash();
+        let ty = self.ty;
+        let read = if let Some(Conversion::Key(key)) = &self.conversion {
+            quote! {
+                #struct_data.read_child_diagnostic_with(reader, #hash, ctx, errors, |reader, ctx, errors| {
+                    #path::prc_trait::read_keyed_list_diagnostic(reader, ctx, #key, errors)
+                })
+            }
+        } else if let Some(conversion) = &self.conversion {
+            // without a fallback value, a failed conversion fails the whole struct
+            let read = conversion.read_fn(path, ty);
+            quote! {
+                match #struct_data.read_child_with(reader, #hash, ctx, #read) {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        errors.push(e);
+                        None
+                    }
+                }
+            }
+        } else if self.coerce {
+            quote! {
+                <#path::prc_trait::Coerce<#ty> as #path::prc_trait::Prc>::read_from_struct_diagnostic(reader, #hash, ctx, #struct_data, errors)
+                    .map(|value| value.0)
+            }
+        } else {
+            quote!(#path::prc_trait::Prc::read_from_struct_diagnostic(reader, #hash, ctx, #struct_data, errors))
+        };
+        let read = match &self.default {
+            Some(default) => quote! {
+                #path::prc_trait::default_if_missing_diagnostic(#hash, errors, |errors| #read, || #default)
+            },
+            None => read,
+        };
+        let read = match self.version_condition(path) {
+            Some(condition) => {
+                let default = self.default_value();
+                quote! {
+                    match ctx.version() {
+                        Some(__prc_version) if !(#condition) => Some(#default),
+                        __prc_version => #path::prc_trait::require_for_version_diagnostic(
+                            #hash,
+                            __prc_version,
+                            errors,
+                            |errors| #read,
+                            || #default,
+                        ),
+                    }
+                }
+            }
+            None => read,
+        };
+        // an invalid value is still used, so the rest of the file can be checked
+        let read = match self.checks(path, &quote!(__prc_checked)) {
+            Some(checks) => quote! {{
+                let __prc_read: ::std::option::Option<#ty> = #read;
+                if let Some(__prc_checked) = &__prc_read {
+                    if let Err(kind) = #checks {
+                        errors.push(#path::prc_trait::invalid_child(reader, #hash, ctx, #struct_data, kind));
+                    }
+                }
+                __prc_read
+            }},
+            None => read,
+        };
+        let hashes = self.hashes();
+        self.with_hash(quote!(#struct_data.find_hash(reader, &#hashes, ctx)), read)
+    }
+
+    /// The tokens for the field's value in [Prc::fallback], or [None] if
+    /// the field has no fallback value
+    fn fallback(&self, path: &proc_macro2::TokenStream) -> Option<proc_macro2::TokenStream> {
+        match (&self.default, &self.conversion) {
+            (Some(default), _) => Some(default.clone()),
+            (None, Some(Conversion::Key(_))) => Some(quote!(::std::default::Default::default())),
+            // converted fields might not implement Prc
+            (None, Some(_)) => None,
+            (None, None) => Some(quote!(#path::prc_trait::Prc::fallback()?)),
+        }
+    }
+
+    /// The tokens writing `value`, a reference to the field, into `param_struct`
+    fn write(
+        &self,
+        path: &proc_macro2::TokenStream,
+        value: &proc_macro2::TokenStream,
+        param_struct: &proc_macro2::TokenStream,
+    ) -> proc_macro2::TokenStream {
+        let hash = &self.hash;
+        match &self.conversion {
+            Some(Conversion::With(module)) => {
+                quote!((#param_struct).0.push((#hash, #module::to_param(#value)));)
+            }
+            // checked by check_into_fields, so only fields with an 'into' type are written
+            Some(Conversion::From(_)) => {
+                let into = &self.into;
+                quote! {
+                #path::prc_trait::PrcWrite::write_to_struct(
+                    &::std::convert::Into::<#into>::into(::std::clone::Clone::clone(#value)),
+                    #hash,
+                    #param_struct,
+                )?;
+                }
+            }
+            Some(Conversion::Key(_)) => {
+                quote!((#param_struct).0.push((#hash, #path::prc_trait::keyed_list_to_param(#value)?));)
+            }
+            None => {
+                quote!(#path::prc_trait::PrcWrite::write_to_struct(#value, #hash, #param_struct)?;)
+            }
+        }
+    }
+}
+
+/// Reads the attributes of each field
+fn parse_fields<'a>(
+    path: &proc_macro2::TokenStream,
+    fields: &'a Punctuated<Field, Comma>,
+    labels: &LabelRules,
+) -> SynResult<Vec<FieldData<'a>>> {
+    fields
+        .iter()
+        .map(|field| {
+            let attrs = field
+                .attrs
+                .iter()
+                .filter(|attr| attr.path.is_ident("prc"))
+                .map(|attr| {
+                    attr.parse_args_with(Punctuated::<FieldAttribute, Comma>::parse_terminated)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let ident = field.ident.as_ref().unwrap();
+            let label = labels.label(&ident.to_string());
+
+            let mut hash = None;
+            let mut aliases = Vec::new();
+            let mut default = None;
+            let mut coerce = false;
+            let mut borrow = false;
+            let mut rest = false;
+            let mut conversion = None;
+            let mut into = None;
+            let mut range = None;
+            let mut len = None;
+            let mut validate = None;
+            let mut since = None;
+            let mut until = None;
+            let mut predicate = None;
+            let attrs = attrs.into_iter().flatten().collect::<Vec<_>>();
+            let has_rest = attrs
+                .iter()
+                .any(|attr| matches!(attr, FieldAttribute::Rest));
+            if has_rest && attrs.len() > 1 {
+                return Err(syn::Error::new_spanned(field, INVALID_REST_FIELD));
+            }
+            for attr in attrs {
+                let duplicate = match attr {
+                    FieldAttribute::Hash(lit) => hash.replace(lit_hash(path, &lit)).is_some(),
+                    FieldAttribute::Name(name) => {
+                        hash.replace(quote!(#path::hash40::hash40(#name))).is_some()
+                    }
+                    FieldAttribute::Alias(alias) => {
+                        aliases.push(lit_hash(path, &alias));
+                        false
+                    }
+                    FieldAttribute::Default(value) => {
+                        let value = value
+                            .map(|value| quote!(#value))
+                            .unwrap_or_else(|| quote!(::std::default::Default::default()));
+                        if default.replace(value).is_some() {
+                            return Err(syn::Error::new_spanned(field, DUPLICATE_FIELD_ATTR));
+                        }
+                        false
+                    }
+                    FieldAttribute::Coerce => {
+                        if coerce {
+                            return Err(syn::Error::new_spanned(field, DUPLICATE_FIELD_ATTR));
+                        }
+                        coerce = true;
+                        false
+                    }
+                    FieldAttribute::Rest => {
+                        rest = true;
+                        false
+                    }
+                    FieldAttribute::Borrow => {
+                        if borrow {
+                            return Err(syn::Error::new_spanned(field, INVALID_CONVERSION));
+                        }
+                        borrow = true;
+                        false
+                    }
+                    FieldAttribute::With(module) => {
+                        if conversion.replace(Conversion::With(module)).is_some() {
+                            return Err(syn::Error::new_spanned(field, INVALID_CONVERSION));
+                        }
+                        false
+                    }
+                    FieldAttribute::From(ty) | FieldAttribute::TryFrom(ty) => {
+                        if conversion.replace(Conversion::From(ty)).is_some() {
+                            return Err(syn::Error::new_spanned(field, INVALID_CONVERSION));
+                        }
+                        false
+                    }
+                    FieldAttribute::Into(ty) => {
+                        if into.replace(ty).is_some() {
+                            return Err(syn::Error::new_spanned(field, DUPLICATE_FIELD_ATTR));
+                        }
+                        false
+                    }
+                    FieldAttribute::Range(value) => {
+                        if range.replace(value).is_some() {
+                            return Err(syn::Error::new_spanned(field, DUPLICATE_FIELD_ATTR));
+                        }
+                        false
+                    }
+                    FieldAttribute::Len(value) => {
+                        if len.replace(value).is_some() {
+                            return Err(syn::Error::new_spanned(field, DUPLICATE_FIELD_ATTR));
+                        }
+                        false
+                    }
+                    FieldAttribute::Validate(function) => {
+                        if validate.replace(function).is_some() {
+                            return Err(syn::Error::new_spanned(field, DUPLICATE_FIELD_ATTR));
+                        }
+                        false
+                    }
+                    FieldAttribute::Since(version) => {
+                        if since.replace(lit_version(path, &version)).is_some() {
+                            return Err(syn::Error::new_spanned(field, DUPLICATE_FIELD_ATTR));
+                        }
+                        false
+                    }
+                    FieldAttribute::Until(version) => {
+                        if until.replace(lit_version(path, &version)).is_some() {
+                            return Err(syn::Error::new_spanned(field, DUPLICATE_FIELD_ATTR));
+                        }
+                        false
+                    }
+                    FieldAttribute::If(function) => {
+                        if predicate.replace(function).is_some() {
+                            return Err(syn::Error::new_spanned(field, DUPLICATE_FIELD_ATTR));
+                        }
+                        false
+                    }
+                    FieldAttribute::Key(key) => {
+                        if conversion
+                            .replace(Conversion::Key(lit_hash(path, &key)))
+                            .is_some()
+                        {
+                            return Err(syn::Error::new_spanned(field, INVALID_CONVERSION));
+                        }
+                        false
+                    }
+                };
+                if duplicate {
+                    return Err(syn::Error::new_spanned(field, INVALID_FIELD_ATTR_COUNT));
+                }
+            }
+            if [coerce, borrow, conversion.is_some()]
+                .iter()
+                .filter(|set| **set)
+                .count()
+                > 1
+            {
+                return Err(syn::Error::new_spanned(field, INVALID_CONVERSION));
+            }
+            match (&into, &conversion) {
+                (Some(_), Some(Conversion::From(_))) | (None, _) => {}
+                (Some(ty), _) => return Err(syn::Error::new_spanned(ty, INVALID_INTO)),
+            }
+
+            Ok(FieldData {
+                ident,
+                ty: &field.ty,
+                hash: hash.unwrap_or_else(|| quote!(#path::hash40::hash40(#label))),
+                aliases,
+                default,
+                coerce,
+                borrow,
+                rest,
+                conversion,
+                into,
+                range,
+                len,
+                validate,
+                since,
+                until,
+                predicate,
+            })
+        })
+        .collect()
+}
+
+/// Checks that at most one field uses the 'rest' attribute
+fn check_rest_fields(fields: &[FieldData]) -> SynResult<()> {
+    match fields.iter().filter(|field| field.rest).nth(1) {
+        Some(field) => Err(syn::Error::new(field.ident.span(), DUPLICATE_REST_FIELD)),
+        None => Ok(()),
+    }
+}
+
+/// Checks that no field uses the 'borrow' attribute, outside of PrcBorrow
+fn check_borrow_fields(fields: &[FieldData]) -> SynResult<()> {
+    match fields.iter().find(|field| field.borrow) {
+        Some(field) => Err(syn::Error::new(field.ident.span(), BORROW_WITHOUT_DERIVE)),
+        None => Ok(()),
+    }
+}
+
+/// Checks that every 'from' or 'try_from' field has an 'into' type to be
+/// written as, when deriving PrcWrite
+fn check_into_fields(fields: &[FieldData]) -> SynResult<()> {
+    let missing = fields
+        .iter()
+        .find_map(|field| match (&field.conversion, &field.into) {
+            (Some(Conversion::From(from)), None) => Some(from),
+            _ => None,
+        });
+    match missing {
+        Some(from) => Err(syn::Error::new_spanned(from, CONVERSION_WITHOUT_INTO)),
+        None => Ok(()),
+    }
+}
+
+/// Checks that no field uses the 'coerce' attribute, when deriving PrcWrite
+fn check_coerce_fields(fields: &[FieldData]) -> SynResult<()> {
+    match fields.iter().find(|field| field.coerce) {
+        Some(field) => Err(syn::Error::new(field.ident.span(), COERCE_WITH_WRITE)),
+        None => Ok(()),
+    }
+}
+
+/// Checks the attributes involving several fields, and returns the tokens
+/// declaring `__prc_known`, the hashes of the fields, if they're needed to
+/// find the params which don't match one
+fn known_hashes(
+    ident: &Ident,
+    path: &proc_macro2::TokenStream,
+    attrs: &MainAttributes,
+    fields: &[FieldData],
+) -> SynResult<proc_macro2::TokenStream> {
+    check_rest_fields(fields)?;
+    let has_rest = fields.iter().any(|field| field.rest);
+    if has_rest && attrs.deny_unknown_fields {
+        return Err(syn::Error::new(ident.span(), DENY_UNKNOWN_WITH_REST));
+    }
+
+    let known = fields
+        .iter()
+        .filter(|field| !field.rest)
+        .flat_map(|field| std::iter::once(&field.hash).chain(&field.aliases))
+        .collect::<Vec<_>>();
+    let known_len = known.len();
+    if has_rest || attrs.deny_unknown_fields {
+        Ok(quote!(let __prc_known: [#path::hash40::Hash40; #known_len] = [#(#known),*];))
+    } else {
+        Ok(quote!())
+    }
+}
+
+/// The tokens reading the struct from `reader` and `ctx`, shared by
+/// [Prc::read_param] and [PrcBorrow::borrow_param]
+fn read_struct(
+    path: &proc_macro2::TokenStream,
+    attrs: &MainAttributes,
+    fields: &[FieldData],
+    known: &proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    let deny_read = if attrs.deny_unknown_fields {
+        quote!(data.deny_unknown(reader, ctx, &__prc_known)?;)
+    } else {
+        quote!()
+    };
+    let struct_names = fields.iter().map(|field| field.ident);
+    let reads = fields.iter().map(|field| {
+        if field.rest {
+            quote!(data.read_unknown(reader, ctx, &__prc_known)?)
+        } else {
+            field.read(path, &quote!(data))
+        }
+    });
+    quote! {
+        let data = #path::prc_trait::StructData::from_stream(reader)?;
+        #known
+        #deny_read
+        Ok(Self {
+            #(
+                #struct_names: #reads,
+            )*
+        })
+    }
+}
+
+fn derive_named_struct(
+    ident: Ident,
+    attrs: &MainAttributes,
+    fields: &Punctuated<Field, Comma>,
+) -> SynResult<TokenStream> {
+    let path = crate_path(attrs);
+    let fields = parse_fields(&path, fields, &attrs.labels)?;
+    check_borrow_fields(&fields)?;
+    let known = known_hashes(&ident, &path, attrs, &fields)?;
+    let read = read_struct(&path, attrs, &fields, &known);
+
+    let deny_diagnostic = if attrs.deny_unknown_fields {
+        quote!(data.deny_unknown_diagnostic(reader, ctx, &__prc_known, errors);)
+    } else {
+        quote!()
+    };
+
+    let struct_names = fields.iter().map(|field| field.ident).collect::<Vec<_>>();
+    let fallback = match fields
+        .iter()
+        .map(|field| field.fallback(&path))
+        .collect::<Option<Vec<_>>>()
+    {
+        Some(fallbacks) => quote! {
+            Some(Self {
+                #(
+                    #struct_names: #fallbacks,
+                )*
+            })
+        },
+        None => quote!(None),
+    };
+    // prefixed, so the fields can't shadow the function's variables
+    let bindings = fields
+        .iter()
+        .map(|field| format_ident!("__prc_field_{}", field.ident))
+        .collect::<Vec<_>>();
+    let diagnostic_reads = fields.iter().map(|field| {
+        if field.rest {
+            quote! {
+                match data.read_unknown(reader, ctx, &__prc_known) {
+                    Ok(value) => Some(value),
+                    Err(e) => {
+                        errors.push(e);
+                        #path::prc_trait::Prc::fallback()
+                    }
+                }
+            }
+        } else {
+            field.read_diagnostic(&path, &quote!(data))
+        }
+    });
+
+    Ok(quote! {
+        impl #path::prc_trait::Prc for #ident {
+            fn read_param<__S: #path::prc_trait::ParamSource>(reader: &mut __S, ctx: &#path::prc_trait::ReadContext) -> #path::prc_trait::Result<Self> {
+                #read
+            }
+
+            fn fallback() -> ::std::option::Option<Self> {
+                #fallback
+            }
+
+            fn param_number() -> ::std::option::Option<#path::prc_trait::ParamNumber> {
+                ::std::option::Option::Some(#path::prc_trait::ParamNumber::Struct)
+            }
+
+            fn read_param_diagnostic<__S: #path::prc_trait::ParamSource>(
+                reader: &mut __S,
+                ctx: &#path::prc_trait::ReadContext,
+                errors: &mut ::std::vec::Vec<#path::prc_trait::Error>,
+            ) -> ::std::option::Option<Self> {
+                let data = match #path::prc_trait::StructData::from_stream(reader) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        errors.push(e);
+                        return <Self as #path::prc_trait::Prc>::fallback();
+                    }
+                };
+                #known
+                #deny_diagnostic
+                // every field is read before giving up, so all of their errors are found
+                #(
+                    let #bindings = #diagnostic_reads;
+                )*
+                Some(Self {
+                    #(
+                        #struct_names: #bindings?,
+                    )*
+                })
+            }
+        }
+    }
+    .into())
+}
+
+fn derive_borrow(input: TokenStream) -> SynResult<TokenStream> {
+    let input: DeriveInput = syn::parse(input)?;
+    let ident = input.ident;
+    let attrs = parse_struct_attributes(&input.attrs)?;
+    let fields = match &input.data {
+        Data::Struct(data_struct)
+            if attrs.tag.is_none() && attrs.repr.is_none() && !attrs.transparent =>
+        {
+            match &data_struct.fields {
+                Fields::Named(fields) => &fields.named,
+                _ => return Err(syn::Error::new(ident.span(), INVALID_BORROW_STRUCT)),
+            }
+        }
+        _ => return Err(syn::Error::new(ident.span(), INVALID_BORROW_STRUCT)),
+    };
+
+    // the lifetime of the struct is the lifetime of the data, if it has one
+    let generics = &input.generics;
+    let (lifetime, struct_generics) = match generics.params.iter().collect::<Vec<_>>()[..] {
+        [] => (quote!('__prc), quote!()),
+        [GenericParam::Lifetime(def)] => {
+            let lifetime = &def.lifetime;
+            (quote!(#lifetime), quote!(<#lifetime>))
+        }
+        _ => return Err(syn::Error::new(ident.span(), INVALID_BORROW_STRUCT)),
+    };
+
+    let path = crate_path(&attrs);
+    let fields = parse_fields(&path, fields, &attrs.labels)?;
+    let known = known_hashes(&ident, &path, &attrs, &fields)?;
+    let read = read_struct(&path, &attrs, &fields, &known);
+
+    Ok(quote! {
+        impl<#lifetime> #path::prc_trait::PrcBorrow<#lifetime> for #ident #struct_generics {
+            fn borrow_param(
+                reader: &mut ::std::io::Cursor<&#lifetime [u8]>,
+                ctx: &#path::prc_trait::ReadContext,
+            ) -> #path::prc_trait::Result<Self> {
+                #read
+            }
+        }
+    }
+    .into())
+}
+
+fn derive_write_named_struct(
+    ident: Ident,
+    attrs: &MainAttributes,
+    fields: &Punctuated<Field, Comma>,
+) -> SynResult<TokenStream> {
+    let path = crate_path(attrs);
+    let fields = parse_fields(&path, fields, &attrs.labels)?;
+    check_rest_fields(&fields)?;
+    check_coerce_fields(&fields)?;
+    check_into_fields(&fields)?;
+
+    let len = fields.len();
+    // the params of a rest field are written where the field is declared
+    let writes = fields.iter().map(|field| {
+        let ident = field.ident;
+        if field.rest {
+            quote!(param_struct.0.extend(self.#ident.0.iter().cloned());)
+        } else {
+            field.write(&path, &quote!(&self.#ident), &quote!(&mut param_struct))
+        }
+    });
+
+    Ok(quote! {
+        impl #path::prc_trait::PrcWrite for #ident {
+            fn to_param(&self) -> ::std::result::Result<#path::ParamKind, ::std::io::Error> {
+                let mut param_struct = #path::ParamStruct(::std::vec::Vec::with_capacity(#len));
+                #(#writes)*
+                ::std::result::Result::Ok(#path::ParamKind::Struct(param_struct))
+            }
+        }
+    }
+    .into())
+}
+
+/// The struct's only field, as a member for `self.#member`
+fn transparent_member(field: &Field) -> Member {
+    match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(0.into()),
+    }
+}
+
+fn derive_transparent(
+    ident: Ident,
+    attrs: &MainAttributes,
+    field: &Field,
+) -> SynResult<TokenStream> {
+    let path = crate_path(attrs);
+    let member = transparent_member(field);
+    let ty = &field.ty;
+
+    Ok(quote! {
+        impl #path::prc_trait::Prc for #ident {
+            fn read_param<__S: #path::prc_trait::ParamSource>(reader: &mut __S, ctx: &#path::prc_trait::ReadContext) -> #path::prc_trait::Result<Self> {
+                <#ty as #path::prc_trait::Prc>::read_param(reader, ctx).map(|value| Self { #member: value })
+            }
+
+            fn read_from_struct<__S: #path::prc_trait::ParamSource>(
+                reader: &mut __S,
+                hash: #path::hash40::Hash40,
+                ctx: &#path::prc_trait::ReadContext,
+                struct_data: #path::prc_trait::StructData<__S::Node>,
+            ) -> #path::prc_trait::Result<Self> {
+                <#ty as #path::prc_trait::Prc>::read_from_struct(reader, hash, ctx, struct_data)
+                    .map(|value| Self { #member: value })
+            }
+
+            fn fallback() -> ::std::option::Option<Self> {
+                <#ty as #path::prc_trait::Prc>::fallback().map(|value| Self { #member: value })
+            }
+
+            fn param_number() -> ::std::option::Option<#path::prc_trait::ParamNumber> {
+                <#ty as #path::prc_trait::Prc>::param_number()
+            }
+
+            fn read_param_diagnostic<__S: #path::prc_trait::ParamSource>(
+                reader: &mut __S,
+                ctx: &#path::prc_trait::ReadContext,
+                errors: &mut ::std::vec::Vec<#path::prc_trait::Error>,
+            ) -> ::std::option::Option<Self> {
+                <#ty as #path::prc_trait::Prc>::read_param_diagnostic(reader, ctx, errors)
+                    .map(|value| Self { #member: value })
+            }
+
+            fn read_from_struct_diagnostic<__S: #path::prc_trait::ParamSource>(
+                reader: &mut __S,
+                hash: #path::hash40::Hash40,
+                ctx: &#path::prc_trait::ReadContext,
+                struct_data: #path::prc_trait::StructData<__S::Node>,
+                errors: &mut ::std::vec::Vec<#path::prc_trait::Error>,
+            ) -> ::std::option::Option<Self> {
+                <#ty as #path::prc_trait::Prc>::read_from_struct_diagnostic(reader, hash, ctx, struct_data, errors)
+                    .map(|value| Self { #member: value })
+            }
+        }
+    }
+    .into())
+}
+
+fn derive_write_transparent(
+    ident: Ident,
+    attrs: &MainAttributes,
+    field: &Field,
+) -> SynResult<TokenStream> {
+    let path = crate_path(attrs);
+    let member = transparent_member(field);
+
+    Ok(quote! {
+        impl #path::prc_trait::PrcWrite for #ident {
+            fn to_param(&self) -> ::std::result::Result<#path::ParamKind, ::std::io::Error> {
+                #path::prc_trait::PrcWrite::to_param(&self.#member)
+            }
+
+            fn write_to_struct(
+                &self,
+                hash: #path::hash40::Hash40,
+                param_struct: &mut #path::ParamStruct,
+            ) -> ::std::result::Result<(), ::std::io::Error> {
+                #path::prc_trait::PrcWrite::write_to_struct(&self.#member, hash, param_struct)
+            }
+        }
+    }
+    .into())
+}
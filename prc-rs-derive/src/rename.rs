@@ -0,0 +1,127 @@
+// labels derived from the names of fields and variants, for the 'rename_all'
+// and 'prefix' attributes
+
+use syn::{LitStr, Result as SynResult};
+
+const INVALID_RENAME_RULE: &str =
+    "Invalid rename rule. Accepted rules are \"lowercase\", \"UPPERCASE\", \"PascalCase\", \"camelCase\", \"snake_case\", \"SCREAMING_SNAKE_CASE\", \"kebab-case\" and \"SCREAMING-KEBAB-CASE\"";
+
+/// How the words of a name are joined into a label
+#[derive(Clone, Copy)]
+pub(crate) enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    pub(crate) fn from_lit(lit: &LitStr) -> SynResult<Self> {
+        Ok(match lit.value().as_ref() {
+            "lowercase" => RenameRule::Lower,
+            "UPPERCASE" => RenameRule::Upper,
+            "PascalCase" => RenameRule::Pascal,
+            "camelCase" => RenameRule::Camel,
+            "snake_case" => RenameRule::Snake,
+            "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnake,
+            "kebab-case" => RenameRule::Kebab,
+            "SCREAMING-KEBAB-CASE" => RenameRule::ScreamingKebab,
+            _ => return Err(syn::Error::new_spanned(lit, INVALID_RENAME_RULE)),
+        })
+    }
+
+    /// Joins the words of `name` following the rule. Words are separated by
+    /// underscores or start with an uppercase letter, so both snake_case field
+    /// names and PascalCase variant names are split.
+    fn apply(self, name: &str) -> String {
+        let words = split_words(name);
+        let capitalize = |word: &str| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        };
+        match self {
+            RenameRule::Lower => words.concat(),
+            RenameRule::Upper => words.concat().to_uppercase(),
+            RenameRule::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+            RenameRule::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| {
+                    if i == 0 {
+                        word.clone()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .collect(),
+            RenameRule::Snake => words.join("_"),
+            RenameRule::ScreamingSnake => words.join("_").to_uppercase(),
+            RenameRule::Kebab => words.join("-"),
+            RenameRule::ScreamingKebab => words.join("-").to_uppercase(),
+        }
+    }
+}
+
+/// The lowercase words of a name
+fn split_words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        let boundary = c == '_'
+            || (c.is_uppercase()
+                && previous.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit()));
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        if c != '_' {
+            word.extend(c.to_lowercase());
+        }
+        previous = Some(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// The rules deriving the label of a field or variant without a 'name' or
+/// 'hash' attribute
+#[derive(Default)]
+pub(crate) struct LabelRules {
+    pub(crate) rename_all: Option<RenameRule>,
+    pub(crate) prefix: Option<String>,
+}
+
+impl LabelRules {
+    /// The label for the field or variant called `name`. The prefix is added
+    /// after renaming, so it's used exactly as written.
+    pub(crate) fn label(&self, name: &str) -> String {
+        let name = match self.rename_all {
+            Some(rule) => rule.apply(name),
+            None => name.to_string(),
+        };
+        self.add_prefix(name)
+    }
+
+    /// The label for the variant called `name`. Params are labeled in
+    /// snake_case, so that's used when there's no 'rename_all' attribute.
+    pub(crate) fn variant_label(&self, name: &str) -> String {
+        let rule = self.rename_all.unwrap_or(RenameRule::Snake);
+        self.add_prefix(rule.apply(name))
+    }
+
+    fn add_prefix(&self, name: String) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}{}", prefix, name),
+            None => name,
+        }
+    }
+}
@@ -3,11 +3,13 @@ use crate::RefTable;
 use byteorder::{LittleEndian, WriteBytesExt};
 use hash40::{Hash40, WriteHash40};
 use indexmap::IndexSet;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use std::hash::Hash;
 use std::io::{Cursor, Error, Seek, SeekFrom, Write};
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
-enum RefEntry {
+pub(crate) enum RefEntry {
     RString(String),
     RTable(RefTable),
 }
@@ -21,13 +23,36 @@ struct RefEntryWork {
     pub ref_offset: u32,
 }
 
-struct FileData {
-    hashes: IndexSet<Hash40>,
+/// Lists with at least this many children are encoded in parallel
+#[cfg(feature = "rayon")]
+pub(crate) const PARALLEL_THRESHOLD: usize = 64;
+
+struct FileData<'a> {
+    hashes: &'a IndexSet<Hash40>,
     // map of ref-entries to their relative offset
     ref_entries: Vec<RefEntryWork>,
+    // the number of children a list needs to be encoded in parallel
+    #[cfg(feature = "rayon")]
+    parallel_threshold: usize,
 }
 
 pub fn assemble<C>(cursor: &mut C, param: &ParamStruct) -> Result<(), Error>
+where
+    C: Write + Seek,
+{
+    assemble_inner(
+        cursor,
+        param,
+        #[cfg(feature = "rayon")]
+        PARALLEL_THRESHOLD,
+    )
+}
+
+pub(crate) fn assemble_inner<C>(
+    cursor: &mut C,
+    param: &ParamStruct,
+    #[cfg(feature = "rayon")] parallel_threshold: usize,
+) -> Result<(), Error>
 where
     C: Write + Seek,
 {
@@ -42,28 +67,30 @@ where
     iter_struct_hashes(&mut hashes, param, &mut ref_count);
 
     let mut fd = FileData {
-        hashes,
+        hashes: &hashes,
         ref_entries: Vec::with_capacity(ref_count as usize),
+        #[cfg(feature = "rayon")]
+        parallel_threshold,
     };
 
     // TODO: use with_capacity with some reasonable choice
     let mut param_cursor = Cursor::new(Vec::<u8>::new());
     write_param_struct(&mut param_cursor, &mut fd, param)?;
 
-    let file_start = cursor.seek(SeekFrom::Current(0))?;
+    let file_start = cursor.stream_position()?;
     cursor.write_all(MAGIC)?;
 
     let hash_size = 8 * fd.hashes.len() as u32;
     cursor.write_u32::<LittleEndian>(hash_size)?;
     cursor.seek(SeekFrom::Current(4))?;
-    for hash in &fd.hashes {
+    for hash in fd.hashes {
         cursor.write_hash40::<LittleEndian>(*hash)?;
     }
 
     handle_ref_entries(&mut fd);
     write_ref_entries(cursor, &mut param_cursor, &fd)?;
 
-    let param_pos = cursor.seek(SeekFrom::Current(0))?;
+    let param_pos = cursor.stream_position()?;
     let ref_size = (param_pos - (file_start + 0x10 + hash_size as u64)) as u32;
     // finish writing header
     cursor.seek(SeekFrom::Start(file_start + 0xc))?;
@@ -158,7 +185,7 @@ where
             param_cursor.write_u8(10)?;
             fd.ref_entries.push(RefEntryWork {
                 ref_entry: RefEntry::RString(String::from(val)),
-                param_offset: param_cursor.seek(SeekFrom::Current(0))? as u32,
+                param_offset: param_cursor.stream_position()? as u32,
                 is_duplicate: false,
                 ref_offset: 0,
             });
@@ -166,13 +193,19 @@ where
             Ok(())
         }
         ParamKind::List(val) => {
-            let start_pos = param_cursor.seek(SeekFrom::Current(0))? as u32;
+            let start_pos = param_cursor.stream_position()? as u32;
 
             param_cursor.write_u8(11)?;
             param_cursor.write_u32::<LittleEndian>(val.0.len() as u32)?;
 
             let mut table_pos = start_pos + 5;
             let mut param_pos = table_pos + (4 * val.0.len() as u32);
+
+            #[cfg(feature = "rayon")]
+            if val.0.len() >= fd.parallel_threshold {
+                return write_list_parallel(param_cursor, fd, val, table_pos, param_pos);
+            }
+
             for p in &val.0 {
                 param_cursor.seek(SeekFrom::Start(table_pos as u64))?;
                 param_cursor.write_u32::<LittleEndian>(param_pos - start_pos)?;
@@ -180,11 +213,12 @@ where
 
                 param_cursor.seek(SeekFrom::Start(param_pos as u64))?;
                 write_param(param_cursor, fd, p)?;
-                param_pos = param_cursor.seek(SeekFrom::Current(0))? as u32;
+                param_pos = param_cursor.stream_position()? as u32;
             }
             Ok(())
         }
         ParamKind::Struct(val) => write_param_struct(param_cursor, fd, val),
+        ParamKind::Placeholder(val) => Err(val.write_error()),
     }
 }
 
@@ -196,7 +230,7 @@ fn write_param_struct<C>(
 where
     C: Write + Seek,
 {
-    let start_pos = param_cursor.seek(SeekFrom::Current(0))? as u32;
+    let start_pos = param_cursor.stream_position()? as u32;
 
     param_cursor.write_u8(12)?;
     param_cursor.write_u32::<LittleEndian>(param_struct.0.len() as u32)?;
@@ -221,7 +255,7 @@ where
         if let RefEntry::RTable(ref mut t) = &mut fd.ref_entries[ref_index].ref_entry {
             t.push((
                 fd.hashes.get_full(hash).unwrap().0 as u32,
-                param_cursor.seek(SeekFrom::Current(0))? as u32 - start_pos,
+                param_cursor.stream_position()? as u32 - start_pos,
             ));
         } else {
             unreachable!()
@@ -232,6 +266,55 @@ where
     Ok(())
 }
 
+/// Encodes each child of a list into its own buffer, then stitches the buffers
+/// into the list. Offsets inside a child are relative to the child, except the
+/// ref-entry positions, which are shifted to where the child ends up.
+#[cfg(feature = "rayon")]
+fn write_list_parallel<C>(
+    param_cursor: &mut C,
+    fd: &mut FileData,
+    list: &ParamList,
+    mut table_pos: u32,
+    mut param_pos: u32,
+) -> Result<(), Error>
+where
+    C: Write + Seek,
+{
+    let start_pos = table_pos - 5;
+    let (hashes, parallel_threshold) = (fd.hashes, fd.parallel_threshold);
+
+    let children = list
+        .0
+        .par_iter()
+        .map(|p| {
+            let mut child_cursor = Cursor::new(Vec::<u8>::new());
+            let mut child_fd = FileData {
+                hashes,
+                ref_entries: Vec::new(),
+                parallel_threshold,
+            };
+            write_param(&mut child_cursor, &mut child_fd, p)?;
+            Ok((child_cursor.into_inner(), child_fd.ref_entries))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    for (bytes, ref_entries) in children {
+        param_cursor.seek(SeekFrom::Start(table_pos as u64))?;
+        param_cursor.write_u32::<LittleEndian>(param_pos - start_pos)?;
+        table_pos += 4;
+
+        param_cursor.seek(SeekFrom::Start(param_pos as u64))?;
+        param_cursor.write_all(&bytes)?;
+        fd.ref_entries
+            .extend(ref_entries.into_iter().map(|mut entry| {
+                entry.param_offset += param_pos;
+                entry
+            }));
+        param_pos += bytes.len() as u32;
+    }
+    Ok(())
+}
+
 fn handle_ref_entries(fd: &mut FileData) {
     let entries = &mut fd.ref_entries;
     let mut offset = 0u32;
@@ -1,21 +1,115 @@
 use crate::param::*;
+use crate::prc_trait::ErrorPathPart;
 use crate::RefTable;
 use byteorder::{LittleEndian, ReadBytesExt};
 use hash40::{Hash40, ReadHash40};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use std::collections::HashMap;
-use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
+use std::io::{Cursor, Error, ErrorKind, Read, Seek, SeekFrom};
 use std::rc::Rc;
 
+/// The deepest lists and structs can be nested. Offsets in a damaged file can
+/// make params nest without end, which would overflow the stack.
+const MAX_DEPTH: usize = 512;
+
+/// Lists with at least this many children are decoded in parallel
+#[cfg(feature = "rayon")]
+pub(crate) const PARALLEL_THRESHOLD: usize = 64;
+
+/// The whole file, so parallel tasks can each create their own cursor, and
+/// the number of children a list needs to be decoded in parallel
+#[cfg(feature = "rayon")]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Parallel<'a> {
+    pub data: &'a [u8],
+    pub threshold: usize,
+}
+
 #[derive(Debug)]
-struct FileData {
+struct FileData<'a> {
     ref_start: u32,
-    param_start: u32,
-    hash_table: Vec<Hash40>,
+    hash_table: &'a [Hash40],
     //maps an offset to an index in a list of ref-tables
     ref_tables: HashMap<u32, Rc<RefTable>>,
+    // lists are only decoded in parallel when the file is a slice
+    #[cfg(feature = "rayon")]
+    parallel: Option<Parallel<'a>>,
+    // only collected in lenient mode. Otherwise the first error is returned
+    diagnostics: Option<Vec<Diagnostic>>,
+    // path to the param being read, only tracked in lenient mode
+    path: Vec<ErrorPathPart>,
+    // positions of the lists and structs containing the param being read,
+    // so offsets pointing back to one of them are caught
+    ancestors: Vec<u64>,
+}
+
+/// The result of reading a damaged param file in lenient mode.
+/// Contains every param which could be decoded, with a [Placeholder] in place
+/// of each of the others, and the reasons they couldn't be decoded.
+#[derive(Debug)]
+pub struct Recovered {
+    pub param: ParamStruct,
+    /// Problems found in the file, in the order they were encountered
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A param which couldn't be decoded. Struct entries whose hash index is
+/// outside of the hash table are left out, since they have no key, and the
+/// diagnostic points to the struct instead.
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// The path to the param from the root
+    pub path: Vec<ErrorPathPart>,
+    /// The position of the param in the file
+    pub position: u64,
+    pub error: Error,
 }
 
 pub fn disassemble<C>(cursor: &mut C) -> Result<ParamStruct, Error>
+where
+    C: Read + Seek,
+{
+    disassemble_inner(
+        cursor,
+        #[cfg(feature = "rayon")]
+        None,
+        false,
+    )
+    .map(|recovered| recovered.param)
+}
+
+pub fn disassemble_lenient<C>(cursor: &mut C) -> Result<Recovered, Error>
+where
+    C: Read + Seek,
+{
+    disassemble_inner(
+        cursor,
+        #[cfg(feature = "rayon")]
+        None,
+        true,
+    )
+}
+
+/// Reads a file which is already in memory. Unlike a reader, the slice can be
+/// shared between threads, so large lists are decoded in parallel with `rayon`.
+pub fn disassemble_slice(data: &[u8], lenient: bool) -> Result<Recovered, Error> {
+    disassemble_inner(
+        &mut Cursor::new(data),
+        #[cfg(feature = "rayon")]
+        Some(Parallel {
+            data,
+            threshold: PARALLEL_THRESHOLD,
+        }),
+        lenient,
+    )
+}
+
+pub(crate) fn disassemble_inner<C>(
+    cursor: &mut C,
+    #[cfg(feature = "rayon")] parallel: Option<Parallel>,
+    lenient: bool,
+) -> Result<Recovered, Error>
 where
     C: Read + Seek,
 {
@@ -29,20 +123,27 @@ where
     let hashnum = (hashsize / 8) as usize;
     let refsize = cursor.read_u32::<LittleEndian>()?;
     // using relative addresses allows in-memory read of param files
-    let hash_start = cursor.seek(SeekFrom::Current(0))? as u32;
+    let hash_start = cursor.stream_position()? as u32;
+
+    let ref_start = add_offset(hash_start, hashsize)?;
+    let param_start = add_offset(ref_start, refsize)?;
+
+    let hash_table = (0..hashnum)
+        .map(|_| cursor.read_hash40::<LittleEndian>())
+        .collect::<Result<Vec<_>, _>>()?;
 
     let mut fd = FileData {
-        ref_start: hash_start + hashsize,
-        param_start: hash_start + hashsize + refsize,
-        hash_table: Vec::with_capacity(hashnum),
+        ref_start,
+        hash_table: &hash_table,
         ref_tables: HashMap::new(),
+        #[cfg(feature = "rayon")]
+        parallel,
+        diagnostics: if lenient { Some(Vec::new()) } else { None },
+        path: Vec::new(),
+        ancestors: Vec::new(),
     };
 
-    for _ in 0..hashnum {
-        fd.hash_table.push(cursor.read_hash40::<LittleEndian>()?)
-    }
-
-    cursor.seek(SeekFrom::Start(fd.param_start as u64))?;
+    cursor.seek(SeekFrom::Start(param_start as u64))?;
     let first_byte = cursor.read_u8()?;
     if first_byte != 12 {
         return Err(Error::new(
@@ -53,14 +154,41 @@ where
     cursor.seek(SeekFrom::Current(-1))?;
 
     // TODO: possible cleanup here
-    if let ParamKind::Struct(s) = read_param(cursor, &mut fd)? {
-        Ok(s)
+    if let ParamKind::Struct(param) = read_param(cursor, &mut fd)? {
+        Ok(Recovered {
+            param,
+            diagnostics: fd.diagnostics.unwrap_or_default(),
+        })
     } else {
         //the earlier block guarantees we get a struct
         unreachable!()
     }
 }
 
+/// Reads the param at the reader's position, given the hash table and the
+/// position of the ref table. This is how the [Prc](crate::Prc) trait reads
+/// dynamic params.
+pub(crate) fn read_param_with_tables<C>(
+    cursor: &mut C,
+    hash_table: &[Hash40],
+    ref_table: u64,
+) -> Result<ParamKind, Error>
+where
+    C: Read + Seek,
+{
+    let mut fd = FileData {
+        ref_start: ref_table as u32,
+        hash_table,
+        ref_tables: HashMap::new(),
+        #[cfg(feature = "rayon")]
+        parallel: None,
+        diagnostics: None,
+        path: Vec::new(),
+        ancestors: Vec::new(),
+    };
+    read_param(cursor, &mut fd)
+}
+
 fn read_param<C>(cursor: &mut C, fd: &mut FileData) -> Result<ParamKind, Error>
 where
     C: Read + Seek,
@@ -99,52 +227,61 @@ where
             Ok(ParamKind::Float(val))
         }
         9 => {
-            let val = fd.hash_table[cursor.read_i32::<LittleEndian>()? as usize];
+            let val = get_hash(fd, cursor.read_u32::<LittleEndian>()?)?;
             Ok(ParamKind::Hash(val))
         }
         10 => {
             let strpos = cursor.read_u32::<LittleEndian>()?;
             //remembering where we were is actually unnecessary
             //let curpos = cursor.position();
-            cursor.seek(SeekFrom::Start((fd.ref_start + strpos) as u64))?;
-            let mut val = String::new();
+            cursor.seek(SeekFrom::Start(add_offset(fd.ref_start, strpos)? as u64))?;
+            let mut val = Vec::new();
             let mut next: u8;
             loop {
                 next = cursor.read_u8()?;
                 if next != 0 {
-                    val.push(next as char);
+                    val.push(next);
                 } else {
                     break;
                 }
             }
             //cursor.set_position(curpos);
-            Ok(ParamKind::Str(val))
+            Ok(ParamKind::Str(decode_string(val)))
         }
         11 => {
-            let pos = cursor.seek(SeekFrom::Current(0))? - 1;
+            let pos = cursor.stream_position()? - 1;
             let size = cursor.read_u32::<LittleEndian>()?;
 
-            let params = (0..size)
+            let offsets = (0..size)
                 .map(|_| cursor.read_u32::<LittleEndian>())
-                .collect::<Result<Vec<_>, _>>()?
-                .into_iter()
-                .map(|offset| {
-                    cursor.seek(SeekFrom::Start(pos + offset as u64))?;
-                    read_param(cursor, fd)
-                })
                 .collect::<Result<Vec<_>, _>>()?;
 
+            #[cfg(feature = "rayon")]
+            if let Some(parallel) = fd.parallel {
+                if offsets.len() >= parallel.threshold {
+                    return read_list_parallel(pos, &offsets, fd, parallel);
+                }
+            }
+
+            let mut params = Vec::with_capacity(offsets.len());
+            fd.ancestors.push(pos);
+            for (i, offset) in offsets.into_iter().enumerate() {
+                let part = ErrorPathPart::Index(i as u32);
+                params.push(read_child(cursor, fd, pos + offset as u64, part)?);
+            }
+            fd.ancestors.pop();
+
             Ok(ParamKind::List(ParamList(params)))
         }
         12 => {
-            let pos = cursor.seek(SeekFrom::Current(0))? - 1;
-            let size = cursor.read_u32::<LittleEndian>().unwrap() as usize;
-            let refpos = cursor.read_u32::<LittleEndian>().unwrap();
+            let pos = cursor.stream_position()? - 1;
+            let size = cursor.read_u32::<LittleEndian>()? as usize;
+            let refpos = cursor.read_u32::<LittleEndian>()?;
 
             // '!contains_key' followed by 'insert' is demonstrably faster than using 'entry'
             // despite what the rust clippy feature states
             if !fd.ref_tables.contains_key(&refpos) {
-                cursor.seek(SeekFrom::Start((fd.ref_start + refpos) as u64))?;
+                cursor.seek(SeekFrom::Start(add_offset(fd.ref_start, refpos)? as u64))?;
                 let mut new_table = (0..size)
                     .map(|_| {
                         Ok((
@@ -159,13 +296,28 @@ where
 
             let table = Rc::clone(fd.ref_tables.get(&refpos).unwrap());
 
-            let params = table
-                .iter()
-                .map(|&(hash_index, offset)| {
-                    cursor.seek(SeekFrom::Start(pos + offset as u64))?;
-                    Ok((fd.hash_table[hash_index as usize], read_param(cursor, fd)?))
-                })
-                .collect::<Result<Vec<_>, Error>>()?;
+            let mut params = Vec::with_capacity(table.len());
+            fd.ancestors.push(pos);
+            for &(hash_index, offset) in table.iter() {
+                let hash = match get_hash(fd, hash_index) {
+                    Ok(hash) => hash,
+                    Err(error) => match &mut fd.diagnostics {
+                        // without a key, the entry can't be kept as a placeholder
+                        Some(diagnostics) => {
+                            diagnostics.push(Diagnostic {
+                                path: fd.path.clone(),
+                                position: pos,
+                                error,
+                            });
+                            continue;
+                        }
+                        None => return Err(error),
+                    },
+                };
+                let part = ErrorPathPart::Hash(hash);
+                params.push((hash, read_child(cursor, fd, pos + offset as u64, part)?));
+            }
+            fd.ancestors.pop();
 
             // let params = table
             //     .iter()
@@ -185,8 +337,147 @@ where
             ErrorKind::InvalidData,
             format!(
                 "encountered invalid param number at position: {}",
-                cursor.seek(SeekFrom::Current(0))? - 1
+                cursor.stream_position()? - 1
             ),
         )),
     }
 }
+
+fn get_hash(fd: &FileData, index: u32) -> Result<Hash40, Error> {
+    fd.hash_table.get(index as usize).copied().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("hash index {} is outside of the hash table", index),
+        )
+    })
+}
+
+/// Offsets in the header and the params are relative to the start of a table,
+/// and a damaged file can point past the largest position
+fn add_offset(start: u32, offset: u32) -> Result<u32, Error> {
+    start.checked_add(offset).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "offset {:#x} from position {:#x} is too large",
+                offset, start
+            ),
+        )
+    })
+}
+
+/// Reads the child param at the given position. In lenient mode, any error
+/// is recorded as a diagnostic instead, and the child is replaced by a
+/// [Placeholder].
+fn read_child<C>(
+    cursor: &mut C,
+    fd: &mut FileData,
+    position: u64,
+    part: ErrorPathPart,
+) -> Result<ParamKind, Error>
+where
+    C: Read + Seek,
+{
+    let lenient = fd.diagnostics.is_some();
+    if lenient {
+        fd.path.push(part);
+    }
+    let depth = fd.ancestors.len();
+    let result = if fd.ancestors.contains(&position) {
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("param at position {:#x} contains itself", position),
+        ))
+    } else if depth >= MAX_DEPTH {
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "params are nested more than {} levels deep at position {:#x}",
+                MAX_DEPTH, position
+            ),
+        ))
+    } else {
+        cursor
+            .seek(SeekFrom::Start(position))
+            .and_then(|_| read_param(cursor, fd))
+    };
+    // an error can leave the ancestors of the failed param behind
+    fd.ancestors.truncate(depth);
+    if !lenient {
+        return result;
+    }
+
+    let path = fd.path.clone();
+    fd.path.pop();
+    let error = match result {
+        Ok(param) => return Ok(param),
+        Err(error) => error,
+    };
+    let placeholder = Placeholder {
+        position,
+        error: error.to_string(),
+    };
+    if let Some(diagnostics) = &mut fd.diagnostics {
+        diagnostics.push(Diagnostic {
+            path,
+            position,
+            error,
+        });
+    }
+    Ok(ParamKind::Placeholder(placeholder))
+}
+
+/// Decodes each child of a list on its own cursor. Every task keeps a separate
+/// ref-table cache, since the children don't depend on each other.
+#[cfg(feature = "rayon")]
+fn read_list_parallel(
+    pos: u64,
+    offsets: &[u32],
+    fd: &mut FileData,
+    parallel: Parallel,
+) -> Result<ParamKind, Error> {
+    let (ref_start, hash_table) = (fd.ref_start, fd.hash_table);
+    let lenient = fd.diagnostics.is_some();
+    let path = &fd.path;
+    let mut ancestors = fd.ancestors.clone();
+    ancestors.push(pos);
+    let ancestors = &ancestors;
+
+    let children = offsets
+        .par_iter()
+        .enumerate()
+        .map(|(i, &offset)| {
+            let mut cursor = Cursor::new(parallel.data);
+            let mut fd = FileData {
+                ref_start,
+                hash_table,
+                ref_tables: HashMap::new(),
+                parallel: Some(parallel),
+                diagnostics: if lenient { Some(Vec::new()) } else { None },
+                path: path.clone(),
+                ancestors: ancestors.clone(),
+            };
+            let part = ErrorPathPart::Index(i as u32);
+            let child = read_child(&mut cursor, &mut fd, pos + offset as u64, part)?;
+            Ok((child, fd.diagnostics))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let mut params = Vec::with_capacity(children.len());
+    for (child, diagnostics) in children {
+        params.push(child);
+        if let (Some(all), Some(found)) = (&mut fd.diagnostics, diagnostics) {
+            all.extend(found);
+        }
+    }
+
+    Ok(ParamKind::List(ParamList(params)))
+}
+
+/// Decodes the bytes of a string param. Strings are written as UTF-8, but a
+/// string which isn't valid UTF-8 is still read, with one character per byte
+/// as older versions did, so those files keep loading.
+pub(crate) fn decode_string(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes)
+        .unwrap_or_else(|e| e.into_bytes().into_iter().map(char::from).collect())
+}
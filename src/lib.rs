@@ -1,7 +1,11 @@
 mod asm;
 mod disasm;
+#[cfg(feature = "mmap")]
+mod mmap;
 mod param;
+mod param_serde;
 pub mod prc_trait;
+mod size;
 #[cfg(feature = "xml-feat")]
 pub mod xml;
 
@@ -12,10 +16,18 @@ use std::fs::{read, write};
 use std::io::{Cursor, Error, Read, Seek, Write};
 use std::path::Path;
 
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+pub use disasm::{Diagnostic, Recovered};
 pub use hash40;
+#[cfg(feature = "mmap")]
+pub use mmap::MappedParamFile;
 pub use param::*;
-pub use prc_rs_derive::Prc;
-pub use prc_trait::Prc;
+pub use param_serde::{from_param, serde_hash, to_param, Error as SerdeError};
+pub use prc_rs_derive::{Prc, PrcBorrow, PrcWrite};
+pub use prc_trait::{Prc, PrcBorrow, PrcWrite};
+pub use size::{SizeBreakdown, SubtreeSize};
 
 pub(crate) type RefTable = Vec<(u32, u32)>;
 
@@ -29,6 +41,25 @@ where
     disasm::disassemble(reader)
 }
 
+/// Reads a param file which is already in memory, such as a memory-mapped
+/// file. With the `rayon` feature, large lists are decoded in parallel,
+/// which isn't possible with a reader.
+/// Returns a [ParamStruct] if successful, otherwise an [Error].
+pub fn read_slice(data: &[u8]) -> std::result::Result<param::ParamStruct, Error> {
+    disasm::disassemble_slice(data, false).map(|recovered| recovered.param)
+}
+
+/// Reads a param file from the given reader (requires [Seek]), recovering
+/// as much as possible from damaged files. Params which can't be decoded are
+/// replaced by a [Placeholder] and recorded as a [Diagnostic].
+/// Returns an [Error] only if the header or the root struct can't be read.
+pub fn read_stream_lenient<R>(reader: &mut R) -> std::result::Result<Recovered, Error>
+where
+    R: Read + Seek,
+{
+    disasm::disassemble_lenient(reader)
+}
+
 /// Attempts to write a param file into the given writer (requires [Seek]).
 /// Returns nothing if successful, otherwise an [Error].
 pub fn write_stream<W>(
@@ -41,11 +72,39 @@ where
     asm::assemble(writer, param_struct)
 }
 
+/// Computes the exact size in bytes of the file [write_stream] would produce,
+/// without writing anything.
+pub fn encoded_size(param_struct: &param::ParamStruct) -> u64 {
+    size::size_breakdown(param_struct).total() as u64
+}
+
+/// Computes the bytes each subtree of the param adds to the hash table, the
+/// ref table, and the param section when written with [write_stream].
+pub fn size_breakdown(param_struct: &param::ParamStruct) -> SizeBreakdown {
+    size::size_breakdown(param_struct)
+}
+
 /// Attempts to read a param file from the given filepath.
 /// Returns a [ParamStruct] if successful, otherwise an [Error].
 pub fn open<P: AsRef<Path>>(filepath: P) -> std::result::Result<param::ParamStruct, Error> {
     let buf = read(filepath)?;
-    disasm::disassemble(&mut Cursor::new(buf))
+    read_slice(&buf)
+}
+
+/// Reads a param file from the given filepath, recovering as much as possible
+/// from damaged files. See [read_stream_lenient].
+pub fn open_lenient<P: AsRef<Path>>(filepath: P) -> std::result::Result<Recovered, Error> {
+    let buf = read(filepath)?;
+    disasm::disassemble_slice(&buf, true)
+}
+
+/// Attempts to open a param file from the given filepath as a memory map.
+/// The returned handle can be decoded into a [ParamStruct], or read through
+/// a slice reader with the [Prc] trait.
+/// Returns a [MappedParamFile] if successful, otherwise an [Error].
+#[cfg(feature = "mmap")]
+pub fn open_mmap<P: AsRef<Path>>(filepath: P) -> std::result::Result<MappedParamFile, Error> {
+    MappedParamFile::open(filepath)
 }
 
 /// Attempts to write a param file into the given filepath.
@@ -56,5 +115,34 @@ pub fn save<P: AsRef<Path>>(
 ) -> std::result::Result<(), Error> {
     let mut writer = Cursor::new(Vec::<u8>::new());
     asm::assemble(&mut writer, param)?;
-    write(filepath, &writer.into_inner())
+    write(filepath, writer.into_inner())
+}
+
+/// Attempts to read a param file from the given async reader.
+/// The file is buffered into memory first, because reading params requires seeking.
+/// Returns a [ParamStruct] if successful, otherwise an [Error].
+#[cfg(feature = "async")]
+pub async fn read_async<R>(reader: &mut R) -> std::result::Result<param::ParamStruct, Error>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).await?;
+    read_slice(&buf)
+}
+
+/// Attempts to write a param file into the given async writer.
+/// The file is assembled in memory first, then written all at once.
+/// Returns nothing if successful, otherwise an [Error].
+#[cfg(feature = "async")]
+pub async fn write_async<W>(
+    writer: &mut W,
+    param_struct: &param::ParamStruct,
+) -> std::result::Result<(), Error>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = Cursor::new(Vec::<u8>::new());
+    asm::assemble(&mut buf, param_struct)?;
+    writer.write_all(&buf.into_inner()).await
 }
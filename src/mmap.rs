@@ -0,0 +1,63 @@
+use crate::disasm;
+use crate::param::ParamStruct;
+use crate::prc_trait::{self, PrcBorrow};
+
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{Cursor, Error};
+use std::ops::Deref;
+use std::path::Path;
+
+/// A param file backed by a memory map, created with [open_mmap](crate::open_mmap).
+/// Dereferences to the raw bytes of the file.
+#[derive(Debug)]
+pub struct MappedParamFile {
+    map: Mmap,
+}
+
+impl MappedParamFile {
+    pub(crate) fn open<P: AsRef<Path>>(filepath: P) -> Result<Self, Error> {
+        let file = File::open(filepath)?;
+        // The map is only read from. Modifying the file from elsewhere while
+        // it is mapped is undefined behavior, as with any memory map.
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(Self { map })
+    }
+
+    /// The raw bytes of the file
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Creates a reader over the mapped bytes, which can be passed to
+    /// [read_stream](crate::read_stream) or [Prc::read_file](crate::Prc::read_file)
+    pub fn reader(&self) -> Cursor<&[u8]> {
+        Cursor::new(&self.map)
+    }
+
+    /// Decodes the whole file into a [ParamStruct].
+    /// Returns an [Error] if the file isn't a valid param file.
+    pub fn decode(&self) -> Result<ParamStruct, Error> {
+        disasm::disassemble_slice(&self.map, false).map(|recovered| recovered.param)
+    }
+
+    /// Reads a [PrcBorrow] type which borrows strings and lists straight
+    /// from the mapped bytes, so nothing is copied out of the file
+    pub fn borrow<'a, T: PrcBorrow<'a>>(&'a self) -> prc_trait::Result<T> {
+        T::borrow_file(&self.map)
+    }
+}
+
+impl Deref for MappedParamFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.map
+    }
+}
+
+impl AsRef<[u8]> for MappedParamFile {
+    fn as_ref(&self) -> &[u8] {
+        &self.map
+    }
+}
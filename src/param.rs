@@ -9,7 +9,11 @@ const UNWRAP_ERR: &str = "Tried to unwrap param into inconsistent type";
 
 /// The central data structure to param files and params.
 /// Similar to tree-like recursive data formats such as JSON.
+///
+/// New kinds of params may be added, so matches outside of this crate need a
+/// wildcard arm.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum ParamKind {
     // index starts at 1
     Bool(bool),
@@ -21,9 +25,15 @@ pub enum ParamKind {
     U32(u32),
     Float(f32),
     Hash(Hash40),
+    /// A string, which is stored as UTF-8 in files. A string which isn't
+    /// valid UTF-8 is read with one character per byte instead.
     Str(String),
     List(ParamList),
     Struct(ParamStruct),
+    /// A param which couldn't be decoded when reading in lenient mode. It
+    /// keeps the place of the param, so list indices don't shift, but it
+    /// can't be written back to a file.
+    Placeholder(Placeholder),
 }
 
 /// A list of params.
@@ -38,6 +48,29 @@ pub struct ParamList(pub Vec<ParamKind>);
 #[serde(transparent)]
 pub struct ParamStruct(pub Vec<(Hash40, ParamKind)>);
 
+/// Stands in for a param which couldn't be decoded.
+/// See [read_stream_lenient](crate::read_stream_lenient).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Placeholder {
+    /// The position of the param in the file
+    pub position: u64,
+    /// Why the param couldn't be decoded
+    pub error: String,
+}
+
+impl Placeholder {
+    /// The error returned when trying to write the placeholder
+    pub(crate) fn write_error(&self) -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "can't write the placeholder for the param at position {:#x}: {}",
+                self.position, self.error
+            ),
+        )
+    }
+}
+
 impl ParamKind {
     /// Attempts to convert an owned param into the contained value.
     /// Returns an error if the contained value is not the expected type.
@@ -0,0 +1,236 @@
+use super::{hash_from_name, Error, HASH_TOKEN};
+use crate::param::ParamKind;
+use hash40::Hash40;
+use serde::de::{self, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::forward_to_deserialize_any;
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl<'de> de::Deserializer<'de> for &'de ParamKind {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self {
+            ParamKind::Bool(v) => visitor.visit_bool(*v),
+            ParamKind::I8(v) => visitor.visit_i8(*v),
+            ParamKind::U8(v) => visitor.visit_u8(*v),
+            ParamKind::I16(v) => visitor.visit_i16(*v),
+            ParamKind::U16(v) => visitor.visit_u16(*v),
+            ParamKind::I32(v) => visitor.visit_i32(*v),
+            ParamKind::U32(v) => visitor.visit_u32(*v),
+            ParamKind::Float(v) => visitor.visit_f32(*v),
+            // hashes are given as labels, which the Hash40 type can parse back
+            ParamKind::Hash(v) => visitor.visit_string(v.to_label()),
+            ParamKind::Str(v) => visitor.visit_borrowed_str(v),
+            ParamKind::List(v) => visitor.visit_seq(ListAccess { iter: v.0.iter() }),
+            ParamKind::Struct(v) => visitor.visit_map(StructAccess {
+                iter: v.0.iter(),
+                fields: &[],
+                value: None,
+            }),
+            ParamKind::Placeholder(v) => Err(Error::new(format!(
+                "param at position {:#x} couldn't be decoded: {}",
+                v.position, v.error
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        // missing keys are handled by the struct, so any param present is Some
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        if name == HASH_TOKEN {
+            return self.deserialize_any(visitor);
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self {
+            ParamKind::Struct(v) => visitor.visit_map(StructAccess {
+                iter: v.0.iter(),
+                fields,
+                value: None,
+            }),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let (variant, content) = match self {
+            ParamKind::Hash(hash) => (*hash, None),
+            ParamKind::Str(name) => (hash_from_name(name), None),
+            ParamKind::Struct(v) if v.0.len() == 1 => (v.0[0].0, Some(&v.0[0].1)),
+            _ => {
+                return Err(Error::new(
+                    "enums must be a hash, a string, or a struct with one key",
+                ))
+            }
+        };
+        visitor.visit_enum(Enum {
+            variant: Key {
+                hash: variant,
+                fields: variants,
+            },
+            content,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map identifier
+        ignored_any
+    }
+}
+
+struct ListAccess<'de> {
+    iter: std::slice::Iter<'de, ParamKind>,
+}
+
+impl<'de> SeqAccess<'de> for ListAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.iter
+            .next()
+            .map(|param| seed.deserialize(param))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct StructAccess<'de> {
+    iter: std::slice::Iter<'de, (Hash40, ParamKind)>,
+    // the field names of the struct being deserialized, if any
+    fields: &'static [&'static str],
+    value: Option<&'de ParamKind>,
+}
+
+impl<'de> MapAccess<'de> for StructAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.iter.next() {
+            Some((hash, value)) => {
+                self.value = Some(value);
+                seed.deserialize(Key {
+                    hash: *hash,
+                    fields: self.fields,
+                })
+                .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error::new("struct value read before its key"))?;
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// A struct key or enum variant. Hashes matching one of the expected names are
+/// given as that name, and others as their label.
+#[derive(Clone, Copy)]
+struct Key {
+    hash: Hash40,
+    fields: &'static [&'static str],
+}
+
+impl<'de> de::Deserializer<'de> for Key {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self
+            .fields
+            .iter()
+            .find(|field| hash_from_name(field) == self.hash)
+        {
+            Some(field) => visitor.visit_borrowed_str(field),
+            None => visitor.visit_string(self.hash.to_label()),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct Enum<'de> {
+    variant: Key,
+    content: Option<&'de ParamKind>,
+}
+
+impl<'de> EnumAccess<'de> for Enum<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = seed.deserialize(self.variant)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Enum<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.content {
+            None => Ok(()),
+            Some(_) => Err(Error::new("expected a unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        match self.content {
+            Some(content) => seed.deserialize(content),
+            None => Err(Error::new("expected a newtype variant")),
+        }
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        match self.content {
+            Some(content) => de::Deserializer::deserialize_seq(content, visitor),
+            None => Err(Error::new("expected a tuple variant")),
+        }
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.content {
+            Some(content) => de::Deserializer::deserialize_struct(content, "", fields, visitor),
+            None => Err(Error::new("expected a struct variant")),
+        }
+    }
+}
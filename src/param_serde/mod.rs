@@ -0,0 +1,151 @@
+//! Conversion between ordinary serde types and params.
+//!
+//! Structs and maps become [ParamStruct](crate::ParamStruct)s keyed by the
+//! hashed field names, sequences become lists, and scalars keep their type.
+//! Field names like `0x04857fe845` are taken as the hash itself.
+//! Unit enum variants are stored as the hash of the variant name.
+//! [Hash40] serializes to its label, which serde can't tell apart from a
+//! string, so [Hash40] values are recognized by their type and written as
+//! hashes. That includes fields, list elements, map values and [Option]s,
+//! but not wrappers such as [Box] which forward to the [Hash40] inside.
+//! Annotate those with `#[serde(with = "prc::serde_hash")]`, or with
+//! [serde_hash::option] and [serde_hash::vec] for optional fields and lists.
+//! Map keys are always hashes.
+
+mod de;
+mod ser;
+
+use crate::param::ParamKind;
+use hash40::Hash40;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+/// The newtype struct name [serde_hash] uses to mark hashes
+const HASH_TOKEN: &str = "$prc::Hash40";
+
+/// Converts any serializable value into a param.
+/// 64-bit numbers are narrowed to the 32-bit params. Returns an [Error] if
+/// one doesn't fit, if the value contains types which params can't store,
+/// or if the value itself is [None].
+pub fn to_param<T: Serialize + ?Sized>(value: &T) -> Result<ParamKind, Error> {
+    ser::serialize(value)?.ok_or_else(|| Error::new("the top-level value can't be None"))
+}
+
+/// Creates any deserializable value from a param.
+/// Returns an [Error] if the param doesn't match the shape of the type.
+pub fn from_param<T: DeserializeOwned>(param: &ParamKind) -> Result<T, Error> {
+    T::deserialize(param)
+}
+
+/// Serializes and deserializes a [Hash40] field as a hash param.
+/// Use with `#[serde(with = "prc::serde_hash")]`. Other formats see the
+/// same label string the [Hash40] type normally serializes to.
+pub mod serde_hash {
+    use super::HASH_TOKEN;
+    use hash40::Hash40;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &Hash40, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(HASH_TOKEN, &hash.to_label())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hash40, D::Error> {
+        Hash40::deserialize(deserializer)
+    }
+
+    /// Serializes a hash inside a container
+    struct AsHash<'a>(&'a Hash40);
+
+    impl Serialize for AsHash<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            super::serde_hash::serialize(self.0, serializer)
+        }
+    }
+
+    /// [serde_hash](super::serde_hash) for `Option<Hash40>` fields.
+    /// Use with `#[serde(with = "prc::serde_hash::option")]`.
+    pub mod option {
+        use super::AsHash;
+        use hash40::Hash40;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            hash: &Option<Hash40>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match hash {
+                Some(hash) => serializer.serialize_some(&AsHash(hash)),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<Hash40>, D::Error> {
+            Option::<Hash40>::deserialize(deserializer)
+        }
+    }
+
+    /// [serde_hash](super::serde_hash) for `Vec<Hash40>` fields.
+    /// Use with `#[serde(with = "prc::serde_hash::vec")]`.
+    pub mod vec {
+        use super::AsHash;
+        use hash40::Hash40;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            hashes: &[Hash40],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(hashes.iter().map(AsHash))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<Hash40>, D::Error> {
+            Vec::<Hash40>::deserialize(deserializer)
+        }
+    }
+}
+
+/// The error type returned when converting between serde types and params
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    message: String,
+}
+
+impl Error {
+    fn new<T: Display>(message: T) -> Self {
+        Self {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::new(msg)
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::new(msg)
+    }
+}
+
+/// Hashes a field or variant name, map key or hash label. Like the `hash`
+/// attribute of the [Prc](crate::Prc) derive, names in hexadecimal form such
+/// as `0x04857fe845` are used as the hash directly.
+fn hash_from_name(name: &str) -> Hash40 {
+    Hash40::from_hex_str(name).unwrap_or_else(|_| hash40::hash40(name))
+}
@@ -0,0 +1,554 @@
+use super::{hash_from_name, Error, HASH_TOKEN};
+use crate::param::{ParamKind, ParamList, ParamStruct};
+use crate::prc_trait::ParamNumber;
+use hash40::Hash40;
+use serde::ser::{self, Impossible, Serialize};
+use std::convert::TryFrom;
+use std::fmt::Display;
+
+/// Serializes values into params. [None] is returned for [Option::None],
+/// so that structs and maps can leave the key out.
+struct ParamSerializer;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The error for a 64-bit number which doesn't fit in the param it's narrowed to
+fn out_of_range<T: Display, U>(value: T, expected: ParamNumber) -> Result<U> {
+    Err(Error::new(format!(
+        "value {} doesn't fit in {}",
+        value, expected
+    )))
+}
+
+/// Serializes a value into a param. [Hash40] serializes to its label, which
+/// can't be told apart from a string, so it's recognized by its type instead
+/// and written as a hash.
+pub(super) fn serialize<T: Serialize + ?Sized>(value: &T) -> Result<Option<ParamKind>> {
+    // references to hashes are serialized the same way
+    if std::any::type_name::<T>().trim_start_matches('&') == std::any::type_name::<Hash40>() {
+        return serialize_hash(value);
+    }
+    value.serialize(ParamSerializer)
+}
+
+/// Serializes a value which serializes to a hash label as a hash param
+fn serialize_hash<T: Serialize + ?Sized>(value: &T) -> Result<Option<ParamKind>> {
+    match value.serialize(ParamSerializer)? {
+        Some(ParamKind::Str(label)) => Ok(Some(ParamKind::Hash(hash_from_name(&label)))),
+        _ => Err(Error::new("hashes must be serialized from their label")),
+    }
+}
+
+fn required<T: Serialize + ?Sized>(value: &T, container: &str) -> Result<ParamKind> {
+    serialize(value)?
+        .ok_or_else(|| Error::new(format!("None values can't be stored in a {}", container)))
+}
+
+fn wrap_variant(variant: &'static str, param: ParamKind) -> Option<ParamKind> {
+    Some(ParamKind::Struct(ParamStruct(vec![(
+        hash_from_name(variant),
+        param,
+    )])))
+}
+
+impl ser::Serializer for ParamSerializer {
+    type Ok = Option<ParamKind>;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeStruct;
+    type SerializeStruct = SerializeStruct;
+    type SerializeStructVariant = SerializeStruct;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::Bool(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::I8(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::I16(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::I32(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok> {
+        match i32::try_from(v) {
+            Ok(v) => self.serialize_i32(v),
+            Err(_) => out_of_range(v, ParamNumber::I32),
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::U8(v)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::U16(v)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::U32(v)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok> {
+        match u32::try_from(v) {
+            Ok(v) => self.serialize_u32(v),
+            Err(_) => out_of_range(v, ParamNumber::U32),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok> {
+        // precision is lost, but infinity and NaN are kept
+        if v.is_finite() && v.abs() > f32::MAX as f64 {
+            return out_of_range(v, ParamNumber::Float);
+        }
+        self.serialize_f32(v as f32)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::Str(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::Str(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
+        let list = v.iter().map(|byte| ParamKind::U8(*byte)).collect();
+        Ok(Some(ParamKind::List(ParamList(list))))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok> {
+        serialize(value)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::Struct(ParamStruct::default())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok> {
+        Ok(Some(ParamKind::Hash(hash_from_name(variant))))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        if name == HASH_TOKEN {
+            return serialize_hash(value);
+        }
+        serialize(value)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok> {
+        Ok(wrap_variant(variant, required(value, "variant")?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SerializeList {
+            list: Vec::with_capacity(len.unwrap_or_default()),
+            variant: None,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Ok(SerializeList {
+            list: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(SerializeStruct {
+            children: Vec::with_capacity(len.unwrap_or_default()),
+            next_key: None,
+            variant: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeStruct {
+            children: Vec::with_capacity(len),
+            next_key: None,
+            variant: Some(variant),
+        })
+    }
+}
+
+pub(super) struct SerializeList {
+    list: Vec<ParamKind>,
+    // set for tuple variants, which are wrapped in a struct keyed by the variant
+    variant: Option<&'static str>,
+}
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.list.push(required(value, "list")?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<ParamKind>> {
+        let list = ParamKind::List(ParamList(self.list));
+        Ok(match self.variant {
+            Some(variant) => wrap_variant(variant, list),
+            None => Some(list),
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<ParamKind>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<ParamKind>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<ParamKind>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Option<ParamKind>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+pub(super) struct SerializeStruct {
+    children: Vec<(Hash40, ParamKind)>,
+    next_key: Option<Hash40>,
+    // set for struct variants, which are wrapped in a struct keyed by the variant
+    variant: Option<&'static str>,
+}
+
+impl SerializeStruct {
+    fn insert<T: Serialize + ?Sized>(&mut self, hash: Hash40, value: &T) -> Result<()> {
+        // None values are left out of the struct
+        if let Some(param) = serialize(value)? {
+            self.children.push((hash, param));
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<ParamKind>> {
+        let param_struct = ParamKind::Struct(ParamStruct(self.children));
+        Ok(match self.variant {
+            Some(variant) => wrap_variant(variant, param_struct),
+            None => Some(param_struct),
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeStruct {
+    type Ok = Option<ParamKind>;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.next_key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let hash = self
+            .next_key
+            .take()
+            .ok_or_else(|| Error::new("map value serialized before its key"))?;
+        self.insert(hash, value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeStruct {
+    type Ok = Option<ParamKind>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(hash_from_name(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeStruct {
+    type Ok = Option<ParamKind>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.insert(hash_from_name(key), value)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.finish()
+    }
+}
+
+/// Map keys become hashes the same way as field names, so hexadecimal
+/// strings like the ones [Hash40] serializes to are kept as they are.
+struct KeySerializer;
+
+fn key_error<T>() -> Result<T> {
+    Err(Error::new("map keys must be strings or hashes"))
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = Hash40;
+    type Error = Error;
+
+    type SerializeSeq = Impossible<Hash40, Error>;
+    type SerializeTuple = Impossible<Hash40, Error>;
+    type SerializeTupleStruct = Impossible<Hash40, Error>;
+    type SerializeTupleVariant = Impossible<Hash40, Error>;
+    type SerializeMap = Impossible<Hash40, Error>;
+    type SerializeStruct = Impossible<Hash40, Error>;
+    type SerializeStructVariant = Impossible<Hash40, Error>;
+
+    fn serialize_str(self, v: &str) -> Result<Hash40> {
+        Ok(hash_from_name(v))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Hash40> {
+        Ok(hash_from_name(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Hash40> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _: bool) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_i8(self, _: i8) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_i16(self, _: i16) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_i32(self, _: i32) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_i64(self, _: i64) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_u8(self, _: u8) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_u16(self, _: u16) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_u32(self, _: u32) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_u64(self, _: u64) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_f32(self, _: f32) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_f64(self, _: f64) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_char(self, v: char) -> Result<Hash40> {
+        Ok(hash_from_name(&v.to_string()))
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_none(self) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _: &T) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_unit(self) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Hash40> {
+        key_error()
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq> {
+        key_error()
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple> {
+        key_error()
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        key_error()
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        key_error()
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap> {
+        key_error()
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct> {
+        key_error()
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        key_error()
+    }
+}
@@ -0,0 +1,230 @@
+// zero-copy reads, borrowing strings and lists from the bytes of a file
+
+use std::fmt;
+use std::io::{Cursor, ErrorKind as IoErrorKind};
+use std::marker::PhantomData;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use hash40::Hash40;
+
+use super::{
+    check_type, Error, ErrorKind, ErrorPathPart, ListData, ParamNumber, Prc, ReadContext, Result,
+    StructData,
+};
+
+/// A trait for types which borrow from the bytes of a file instead of
+/// allocating, such as `&str` borrowed from the ref table. Only valid UTF-8
+/// strings can be borrowed, while [Prc] reads other strings with one
+/// character per byte, so use [String] for those. The counterpart
+/// to [Prc] for read-only scans. `#[derive(PrcBorrow)]` reads fields with
+/// [Prc], except for those with the `#[prc(borrow)]` attribute.
+pub trait PrcBorrow<'a>: Sized {
+    /// Creates Self from the param at the reader's position, borrowing from
+    /// the data of the reader
+    fn borrow_param(reader: &mut Cursor<&'a [u8]>, ctx: &ReadContext) -> Result<Self>;
+
+    /// Borrowing counterpart to [Prc::read_from_struct]
+    fn borrow_from_struct(
+        reader: &mut Cursor<&'a [u8]>,
+        hash: Hash40,
+        ctx: &ReadContext,
+        struct_data: StructData,
+    ) -> Result<Self> {
+        struct_data.read_child_with(reader, hash, ctx, Self::borrow_param)
+    }
+
+    /// Borrows Self from the bytes of an entire file. Only the hash table is
+    /// loaded, since the ref table can be read from the data directly.
+    fn borrow_file(data: &'a [u8]) -> Result<Self> {
+        let mut reader = Cursor::new(data);
+        let ctx = ReadContext::from_header(&mut reader, false)?;
+        Self::borrow_param(&mut reader, &ctx)
+    }
+}
+
+impl<'a> PrcBorrow<'a> for &'a str {
+    fn borrow_param(reader: &mut Cursor<&'a [u8]>, ctx: &ReadContext) -> Result<Self> {
+        check_type(reader, ParamNumber::String)?;
+        reader.set_position(reader.position() + 1);
+        let str_offset = reader
+            .read_u32::<LittleEndian>()
+            .map_err(|e| Error::new(e, reader))?;
+        let data: &'a [u8] = reader.get_ref();
+        let bytes = data
+            .get((ctx.offsets().ref_table + str_offset as u64) as usize..)
+            .and_then(|bytes| {
+                let len = bytes.iter().position(|byte| *byte == 0)?;
+                Some(&bytes[..len])
+            })
+            .ok_or_else(|| {
+                let e = std::io::Error::new(
+                    IoErrorKind::UnexpectedEof,
+                    "string isn't terminated before the end of the file",
+                );
+                Error::new(e, reader)
+            })?;
+        std::str::from_utf8(bytes).map_err(|e| {
+            let e = std::io::Error::new(IoErrorKind::InvalidData, e);
+            Error::new(e, reader)
+        })
+    }
+}
+
+impl<'a, T: PrcBorrow<'a>> PrcBorrow<'a> for Option<T> {
+    fn borrow_param(reader: &mut Cursor<&'a [u8]>, ctx: &ReadContext) -> Result<Self> {
+        T::borrow_param(reader, ctx).map(Some)
+    }
+
+    fn borrow_from_struct(
+        reader: &mut Cursor<&'a [u8]>,
+        hash: Hash40,
+        ctx: &ReadContext,
+        struct_data: StructData,
+    ) -> Result<Self> {
+        match struct_data.read_child_with(reader, hash, ctx, T::borrow_param) {
+            Ok(value) => Ok(Some(value)),
+            Err(Error {
+                kind: ErrorKind::ParamNotFound(_),
+                ref path,
+                ..
+            }) if path.is_empty() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Collects the borrowed children, so unlike the other implementations this
+/// allocates. Use [BorrowedList] to read a list without allocating.
+impl<'a, T: PrcBorrow<'a>> PrcBorrow<'a> for Vec<T> {
+    fn borrow_param(reader: &mut Cursor<&'a [u8]>, ctx: &ReadContext) -> Result<Self> {
+        BorrowedList::borrow_param(reader, ctx)?
+            .into_iter()
+            .collect()
+    }
+}
+
+macro_rules! impl_borrow_value {
+    ($($param_type:ty),*) => {
+        $(
+            impl<'a> PrcBorrow<'a> for $param_type {
+                fn borrow_param(reader: &mut Cursor<&'a [u8]>, ctx: &ReadContext) -> Result<Self> {
+                    Prc::read_param(reader, ctx)
+                }
+            }
+        )*
+    };
+}
+
+impl_borrow_value!(bool, i8, u8, i16, u16, i32, u32, f32, Hash40);
+
+/// A list which is only decoded as its children are requested, so reading
+/// it doesn't allocate. Errors from the children have paths relative to
+/// this list.
+pub struct BorrowedList<'a, T> {
+    data: &'a [u8],
+    ctx: ReadContext,
+    list_data: ListData,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T: PrcBorrow<'a>> BorrowedList<'a, T> {
+    /// The number of children in the list
+    pub fn len(&self) -> usize {
+        self.list_data.len as usize
+    }
+
+    /// Whether the list has no children
+    pub fn is_empty(&self) -> bool {
+        self.list_data.len == 0
+    }
+
+    /// Decodes the child at `index`, or returns [None] if it's out of bounds
+    pub fn get(&self, index: usize) -> Option<Result<T>> {
+        if index >= self.len() {
+            return None;
+        }
+        let index = index as u32;
+        let mut reader = Cursor::new(self.data);
+        let value = self
+            .list_data
+            .seek_child(&mut reader, index)
+            .and_then(|()| {
+                T::borrow_param(&mut reader, &self.ctx).map_err(|mut e| {
+                    e.path.insert(0, ErrorPathPart::Index(index));
+                    e
+                })
+            });
+        Some(value)
+    }
+
+    /// Decodes each child in order
+    pub fn iter(&self) -> BorrowedIter<'a, T> {
+        self.clone().into_iter()
+    }
+}
+
+impl<'a, T: PrcBorrow<'a>> IntoIterator for BorrowedList<'a, T> {
+    type Item = Result<T>;
+    type IntoIter = BorrowedIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        BorrowedIter {
+            list: self,
+            index: 0,
+        }
+    }
+}
+
+/// An iterator decoding the children of a [BorrowedList]
+#[derive(Debug, Clone)]
+pub struct BorrowedIter<'a, T> {
+    list: BorrowedList<'a, T>,
+    index: usize,
+}
+
+impl<'a, T: PrcBorrow<'a>> Iterator for BorrowedIter<'a, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.list.get(self.index)?;
+        self.index += 1;
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.list.len() - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<'a, T> PrcBorrow<'a> for BorrowedList<'a, T> {
+    fn borrow_param(reader: &mut Cursor<&'a [u8]>, ctx: &ReadContext) -> Result<Self> {
+        Ok(BorrowedList {
+            data: reader.get_ref(),
+            ctx: ctx.clone(),
+            list_data: ListData::from_stream(reader)?,
+            marker: PhantomData,
+        })
+    }
+}
+
+// implemented manually, so T doesn't need to implement these
+
+impl<T> Clone for BorrowedList<'_, T> {
+    fn clone(&self) -> Self {
+        BorrowedList {
+            data: self.data,
+            ctx: self.ctx.clone(),
+            list_data: self.list_data,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for BorrowedList<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BorrowedList")
+            .field("list_data", &self.list_data)
+            .finish()
+    }
+}
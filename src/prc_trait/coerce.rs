@@ -0,0 +1,173 @@
+// integer coercion, for params which changed to another integer type
+
+use std::convert::TryFrom;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use hash40::Hash40;
+
+use super::{
+    wrong_param, Error, ErrorKind, ParamNumber, ParamSource, Prc, ReadContext, Result, StructData,
+};
+use crate::param::ParamKind;
+
+/// A trait for types which can be read from integer params of a different
+/// type than their own, as long as the value fits. Containers pass the
+/// coercion on to their children. The derive macro uses this for fields with
+/// the `#[prc(coerce)]` attribute.
+pub trait Coercible: Prc {
+    /// Coercing counterpart to [Prc::read_param]
+    fn read_param_coerced<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self>;
+
+    /// Coercing counterpart to [Prc::read_from_struct]
+    fn read_from_struct_coerced<S: ParamSource>(
+        source: &mut S,
+        hash: Hash40,
+        ctx: &ReadContext,
+        struct_data: StructData<S::Node>,
+    ) -> Result<Self> {
+        struct_data
+            .read_child::<S, Coerce<Self>>(source, hash, ctx)
+            .map(|value| value.0)
+    }
+}
+
+/// A wrapper which reads T through [Coercible]. It doesn't implement
+/// [PrcWrite](super::PrcWrite), since the value would be written as T instead
+/// of the integer type of the param it was read from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Coerce<T>(pub T);
+
+impl<T: Coercible> Prc for Coerce<T> {
+    fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        T::read_param_coerced(source, ctx).map(Coerce)
+    }
+
+    fn read_from_struct<S: ParamSource>(
+        source: &mut S,
+        hash: Hash40,
+        ctx: &ReadContext,
+        struct_data: StructData<S::Node>,
+    ) -> Result<Self> {
+        T::read_from_struct_coerced(source, hash, ctx, struct_data).map(Coerce)
+    }
+
+    fn fallback() -> Option<Self> {
+        T::fallback().map(Coerce)
+    }
+}
+
+/// Reads any integer param, returning its value and node
+fn read_int<S: ParamSource>(
+    source: &mut S,
+    ctx: &ReadContext,
+    expected: ParamNumber,
+) -> Result<(i64, S::Node)> {
+    let node = source.node()?;
+    let received = source.peek_number()?;
+    let param = match ParamNumber::try_from(received) {
+        Ok(
+            number @ (ParamNumber::I8
+            | ParamNumber::U8
+            | ParamNumber::I16
+            | ParamNumber::U16
+            | ParamNumber::I32
+            | ParamNumber::U32),
+        ) => source.read_value(number, ctx)?,
+        _ => {
+            return Err(Error::new_with_pos(
+                ErrorKind::WrongParamNumber { expected, received },
+                source.position(node),
+            ))
+        }
+    };
+    let value = match param {
+        ParamKind::I8(value) => i64::from(value),
+        ParamKind::U8(value) => i64::from(value),
+        ParamKind::I16(value) => i64::from(value),
+        ParamKind::U16(value) => i64::from(value),
+        ParamKind::I32(value) => i64::from(value),
+        ParamKind::U32(value) => i64::from(value),
+        param => return Err(wrong_param(expected, &param)),
+    };
+    Ok((value, node))
+}
+
+macro_rules! impl_coerce_int {
+    ($(($int_type:ty, $num:path)),*) => {
+        $(
+            impl Coercible for $int_type {
+                fn read_param_coerced<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+                    let (value, node) = read_int(source, ctx, $num)?;
+                    <$int_type>::try_from(value).map_err(|_| {
+                        Error::new_with_pos(
+                            ErrorKind::OutOfRange { value, expected: $num },
+                            source.position(node),
+                        )
+                    })
+                }
+            }
+        )*
+    };
+}
+
+impl_coerce_int!(
+    (i8, ParamNumber::I8),
+    (u8, ParamNumber::U8),
+    (i16, ParamNumber::I16),
+    (u16, ParamNumber::U16),
+    (i32, ParamNumber::I32),
+    (u32, ParamNumber::U32)
+);
+
+impl<T: Coercible> Coercible for Vec<T> {
+    fn read_param_coerced<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        Vec::<Coerce<T>>::read_param(source, ctx)
+            .map(|list| list.into_iter().map(|value| value.0).collect())
+    }
+}
+
+impl<T: Coercible, const N: usize> Coercible for [T; N] {
+    fn read_param_coerced<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        <[Coerce<T>; N]>::read_param(source, ctx).map(|list| list.map(|value| value.0))
+    }
+}
+
+impl<T: Coercible> Coercible for Option<T> {
+    fn read_param_coerced<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        T::read_param_coerced(source, ctx).map(Some)
+    }
+
+    fn read_from_struct_coerced<S: ParamSource>(
+        source: &mut S,
+        hash: Hash40,
+        ctx: &ReadContext,
+        struct_data: StructData<S::Node>,
+    ) -> Result<Self> {
+        Option::<Coerce<T>>::read_from_struct(source, hash, ctx, struct_data)
+            .map(|value| value.map(|value| value.0))
+    }
+}
+
+macro_rules! impl_coerce_pointer {
+    ($($pointer:ident),*) => {
+        $(
+            impl<T: Coercible> Coercible for $pointer<T> {
+                fn read_param_coerced<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+                    T::read_param_coerced(source, ctx).map($pointer::new)
+                }
+
+                fn read_from_struct_coerced<S: ParamSource>(
+                    source: &mut S,
+                    hash: Hash40,
+                    ctx: &ReadContext,
+                    struct_data: StructData<S::Node>,
+                ) -> Result<Self> {
+                    T::read_from_struct_coerced(source, hash, ctx, struct_data).map($pointer::new)
+                }
+            }
+        )*
+    };
+}
+
+impl_coerce_pointer!(Box, Rc, Arc);
@@ -0,0 +1,167 @@
+// implementations for std containers, read from lists or structs
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::hash::BuildHasher;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use hash40::Hash40;
+use indexmap::IndexMap;
+
+use super::{
+    Error, ErrorPathPart, ListData, ParamNumber, ParamSource, Prc, ReadContext, Result, StructData,
+};
+
+impl<T: Prc, const N: usize> Prc for [T; N] {
+    fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        let list_data = ListData::from_stream(source)?;
+        list_data.check_len(source, N as u32)?;
+        let list = (0..list_data.len)
+            .map(|i| list_data.read_child(source, i, ctx))
+            .collect::<Result<Vec<T>>>()?;
+        // the length was checked already
+        Ok(<[T; N]>::try_from(list).ok().unwrap())
+    }
+
+    fn param_number() -> Option<ParamNumber> {
+        Some(ParamNumber::List)
+    }
+}
+
+macro_rules! impl_tuple {
+    ($len:literal: $($name:ident $index:tt),+) => {
+        impl<$($name: Prc),+> Prc for ($($name,)+) {
+            fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+                let list_data = ListData::from_stream(source)?;
+                list_data.check_len(source, $len)?;
+                Ok(($(list_data.read_child::<S, $name>(source, $index, ctx)?,)+))
+            }
+
+            fn param_number() -> Option<ParamNumber> {
+                Some(ParamNumber::List)
+            }
+        }
+    };
+}
+
+impl_tuple!(1: A 0);
+impl_tuple!(2: A 0, B 1);
+impl_tuple!(3: A 0, B 1, C 2);
+impl_tuple!(4: A 0, B 1, C 2, D 3);
+impl_tuple!(5: A 0, B 1, C 2, D 3, E 4);
+impl_tuple!(6: A 0, B 1, C 2, D 3, E 4, F 5);
+impl_tuple!(7: A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_tuple!(8: A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+macro_rules! impl_pointer {
+    ($($pointer:ident),*) => {
+        $(
+            impl<T: Prc> Prc for $pointer<T> {
+                fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+                    T::read_param(source, ctx).map($pointer::new)
+                }
+
+                fn read_from_struct<S: ParamSource>(
+                    source: &mut S,
+                    hash: Hash40,
+                    ctx: &ReadContext,
+                    struct_data: StructData<S::Node>,
+                ) -> Result<Self> {
+                    T::read_from_struct(source, hash, ctx, struct_data).map($pointer::new)
+                }
+
+                fn fallback() -> Option<Self> {
+                    T::fallback().map($pointer::new)
+                }
+
+                fn param_number() -> Option<ParamNumber> {
+                    T::param_number()
+                }
+
+                fn read_param_diagnostic<S: ParamSource>(
+                    source: &mut S,
+                    ctx: &ReadContext,
+                    errors: &mut Vec<Error>,
+                ) -> Option<Self> {
+                    T::read_param_diagnostic(source, ctx, errors).map($pointer::new)
+                }
+
+                fn read_from_struct_diagnostic<S: ParamSource>(
+                    source: &mut S,
+                    hash: Hash40,
+                    ctx: &ReadContext,
+                    struct_data: StructData<S::Node>,
+                    errors: &mut Vec<Error>,
+                ) -> Option<Self> {
+                    T::read_from_struct_diagnostic(source, hash, ctx, struct_data, errors)
+                        .map($pointer::new)
+                }
+            }
+        )*
+    };
+}
+
+impl_pointer!(Box, Rc, Arc);
+
+/// Reads every child of a struct, adding the hash to the error path
+fn read_struct_entries<S: ParamSource, T: Prc>(
+    source: &mut S,
+    ctx: &ReadContext,
+) -> Result<Vec<(Hash40, T)>> {
+    let struct_data = StructData::from_stream(source)?;
+    struct_data
+        .entries(source, ctx)?
+        .into_iter()
+        .map(|(hash, node)| {
+            source.seek_node(node)?;
+            let child = T::read_param(source, ctx).map_err(|mut e| {
+                e.path.insert(0, ErrorPathPart::Hash(hash));
+                e
+            })?;
+            Ok((hash, child))
+        })
+        .collect()
+}
+
+impl<T: Prc, S: BuildHasher + Default> Prc for HashMap<Hash40, T, S> {
+    fn read_param<P: ParamSource>(source: &mut P, ctx: &ReadContext) -> Result<Self> {
+        read_struct_entries(source, ctx).map(|entries| entries.into_iter().collect())
+    }
+
+    fn fallback() -> Option<Self> {
+        Some(Self::default())
+    }
+
+    fn param_number() -> Option<ParamNumber> {
+        Some(ParamNumber::Struct)
+    }
+}
+
+impl<T: Prc> Prc for BTreeMap<Hash40, T> {
+    fn read_param<P: ParamSource>(source: &mut P, ctx: &ReadContext) -> Result<Self> {
+        read_struct_entries(source, ctx).map(|entries| entries.into_iter().collect())
+    }
+
+    fn fallback() -> Option<Self> {
+        Some(Self::default())
+    }
+
+    fn param_number() -> Option<ParamNumber> {
+        Some(ParamNumber::Struct)
+    }
+}
+
+impl<T: Prc, S: BuildHasher + Default> Prc for IndexMap<Hash40, T, S> {
+    fn read_param<P: ParamSource>(source: &mut P, ctx: &ReadContext) -> Result<Self> {
+        read_struct_entries(source, ctx).map(|entries| entries.into_iter().collect())
+    }
+
+    fn fallback() -> Option<Self> {
+        Some(Self::default())
+    }
+
+    fn param_number() -> Option<ParamNumber> {
+        Some(ParamNumber::Struct)
+    }
+}
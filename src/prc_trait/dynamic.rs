@@ -0,0 +1,45 @@
+// implementations for the dynamic param types, so typed and untyped
+// params can be mixed in one struct
+
+use super::{wrong_param, ParamNumber, ParamSource, Prc, ReadContext, Result};
+use crate::param::{ParamKind, ParamList, ParamStruct};
+
+impl Prc for ParamKind {
+    fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        source.read_dynamic(ctx)
+    }
+}
+
+impl Prc for ParamStruct {
+    fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        match source.read_value(ParamNumber::Struct, ctx)? {
+            ParamKind::Struct(param_struct) => Ok(param_struct),
+            param => Err(wrong_param(ParamNumber::Struct, &param)),
+        }
+    }
+
+    fn fallback() -> Option<Self> {
+        Some(ParamStruct(Vec::new()))
+    }
+
+    fn param_number() -> Option<ParamNumber> {
+        Some(ParamNumber::Struct)
+    }
+}
+
+impl Prc for ParamList {
+    fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        match source.read_value(ParamNumber::List, ctx)? {
+            ParamKind::List(list) => Ok(list),
+            param => Err(wrong_param(ParamNumber::List, &param)),
+        }
+    }
+
+    fn fallback() -> Option<Self> {
+        Some(ParamList(Vec::new()))
+    }
+
+    fn param_number() -> Option<ParamNumber> {
+        Some(ParamNumber::List)
+    }
+}
@@ -0,0 +1,146 @@
+// lists of structs read into maps, indexed by one of the struct's children
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::io::Error as IoError;
+use std::iter::FromIterator;
+
+use hash40::Hash40;
+
+use super::{
+    diagnose_child, Error, ErrorKind, ErrorPathPart, ListData, ParamSource, Prc, PrcWrite,
+    ReadContext, Result, StructData,
+};
+use crate::param::{ParamKind, ParamList};
+
+/// Tracks the keys found so far, to report duplicates
+#[derive(Default)]
+struct KeySet(HashMap<Hash40, u32>);
+
+impl KeySet {
+    /// Records the key at `index`, or returns the error kind if an earlier
+    /// child already used it
+    fn insert(&mut self, key: Hash40, index: u32) -> std::result::Result<(), ErrorKind> {
+        match self.0.entry(key) {
+            Entry::Occupied(entry) => Err(ErrorKind::DuplicateKey {
+                key,
+                first_index: *entry.get(),
+            }),
+            Entry::Vacant(entry) => {
+                entry.insert(index);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Reads the `key` child of the struct the source is at, then the whole
+/// struct as T
+fn read_keyed_child<S: ParamSource, T: Prc>(
+    source: &mut S,
+    ctx: &ReadContext,
+    key: Hash40,
+) -> Result<(Hash40, T, S::Node)> {
+    let struct_data = StructData::from_stream(source)?;
+    let child_key = struct_data.read_child::<S, Hash40>(source, key, ctx)?;
+    source.seek_node(struct_data.node)?;
+    let value = T::read_param(source, ctx)?;
+    Ok((child_key, value, struct_data.node))
+}
+
+/// Reads a list of structs into a map, indexed by the hash of each struct's
+/// `key` child. A key used by more than one struct is reported as
+/// [ErrorKind::DuplicateKey]. Used by the derive macro for fields with the
+/// `#[prc(key = "...")]` attribute.
+pub fn read_keyed_list<S, T, M>(source: &mut S, ctx: &ReadContext, key: Hash40) -> Result<M>
+where
+    S: ParamSource,
+    T: Prc,
+    M: IntoIterator<Item = (Hash40, T)> + FromIterator<(Hash40, T)>,
+{
+    let list_data = ListData::from_stream(source)?;
+    let mut keys = KeySet::default();
+    (0..list_data.len)
+        .map(|i| {
+            let add_index = |mut e: Error| {
+                e.path.insert(0, ErrorPathPart::Index(i));
+                e
+            };
+            list_data.seek_child(source, i)?;
+            let (child_key, value, node) = read_keyed_child(source, ctx, key).map_err(add_index)?;
+            keys.insert(child_key, i)
+                .map_err(|kind| add_index(Error::new_with_pos(kind, source.position(node))))?;
+            Ok((child_key, value))
+        })
+        .collect()
+}
+
+/// The diagnostic counterpart to [read_keyed_list]. Every struct is read,
+/// and only the first struct with a duplicate key is kept. A struct which
+/// can't be read is recorded and left out, without failing the whole map.
+pub fn read_keyed_list_diagnostic<S, T, M>(
+    source: &mut S,
+    ctx: &ReadContext,
+    key: Hash40,
+    errors: &mut Vec<Error>,
+) -> Option<M>
+where
+    S: ParamSource,
+    T: Prc,
+    M: IntoIterator<Item = (Hash40, T)> + FromIterator<(Hash40, T)>,
+{
+    let list_data = match ListData::from_stream(source) {
+        Ok(list_data) => list_data,
+        Err(e) => {
+            errors.push(e);
+            return Some(std::iter::empty().collect());
+        }
+    };
+    let mut keys = KeySet::default();
+    let children = (0..list_data.len)
+        .filter_map(|i| {
+            diagnose_child(ErrorPathPart::Index(i), errors, |errors| {
+                let child = list_data
+                    .seek_child(source, i)
+                    .and_then(|()| StructData::from_stream(source))
+                    .and_then(|struct_data| {
+                        let child_key = struct_data.read_child::<S, Hash40>(source, key, ctx)?;
+                        Ok((struct_data, child_key))
+                    });
+                let (struct_data, child_key) = match child {
+                    Ok(child) => child,
+                    Err(e) => {
+                        errors.push(e);
+                        return None;
+                    }
+                };
+                if let Err(e) = source.seek_node(struct_data.node) {
+                    errors.push(e);
+                    return None;
+                }
+                let value = T::read_param_diagnostic(source, ctx, errors)?;
+                match keys.insert(child_key, i) {
+                    Ok(()) => Some((child_key, value)),
+                    Err(kind) => {
+                        // the duplicate is dropped, like children which fail
+                        errors.push(Error::new_with_pos(kind, source.position(struct_data.node)));
+                        None
+                    }
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    Some(children.into_iter().collect())
+}
+
+/// Writes the values of a map read by [read_keyed_list] back into a list.
+/// The key is already a child of each value, so it isn't written separately.
+pub fn keyed_list_to_param<'a, T, M>(map: &'a M) -> std::result::Result<ParamKind, IoError>
+where
+    T: PrcWrite + 'a,
+    &'a M: IntoIterator<Item = (&'a Hash40, &'a T)>,
+{
+    map.into_iter()
+        .map(|(_, value)| value.to_param())
+        .collect::<std::result::Result<_, _>>()
+        .map(|list| ParamKind::List(ParamList(list)))
+}
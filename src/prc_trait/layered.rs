@@ -0,0 +1,240 @@
+// typed reads of a base file with patch files layered over it
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+
+use super::{prepare, Error, ErrorPathPart, ListData, Prc, Result, StructData};
+use crate::param::{ParamKind, ParamStruct};
+
+/// Any reader which can seek, so layers can be read from different types of
+/// readers with [Prc::read_layered]
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+
+/// A value read from a base file and patches, along with the layer which set
+/// each of its params
+#[derive(Debug, Clone)]
+pub struct Layered<T> {
+    pub value: T,
+    pub provenance: Provenance,
+}
+
+/// An error from [Prc::read_layered], with the layer it came from. The
+/// position of the error is in the file of that layer.
+#[derive(Debug)]
+pub struct LayerError {
+    /// The base is layer 0, and each patch is its index plus one
+    pub layer: usize,
+    pub error: Error,
+}
+
+impl fmt::Display for LayerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "layer {}: {}", self.layer, self.error)
+    }
+}
+
+impl std::error::Error for LayerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// The layer which set each param of a [Layered] value. The base is layer 0,
+/// and each patch is its index plus one. Paths are written the same way as
+/// the paths of errors.
+#[derive(Debug, Clone, Default)]
+pub struct Provenance(HashMap<Vec<ErrorPathPart>, usize>);
+
+impl Provenance {
+    /// The layer which set the param at `path`. For structs and lists, this
+    /// is the highest layer which set any of their children.
+    /// Returns [None] if no layer has a param at the path.
+    pub fn layer(&self, path: &[ErrorPathPart]) -> Option<usize> {
+        self.0.get(path).copied()
+    }
+
+    /// The path and layer of every param, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (&[ErrorPathPart], usize)> {
+        self.0.iter().map(|(path, layer)| (path.as_slice(), *layer))
+    }
+
+    /// Records `layer` for the param and all of its children
+    fn mark(&mut self, path: &mut Vec<ErrorPathPart>, param: &ParamKind, layer: usize) {
+        self.0.insert(path.clone(), layer);
+        match param {
+            ParamKind::Struct(param_struct) => {
+                for (hash, child) in &param_struct.0 {
+                    path.push(ErrorPathPart::Hash(*hash));
+                    self.mark(path, child, layer);
+                    path.pop();
+                }
+            }
+            ParamKind::List(list) => {
+                for (i, child) in list.0.iter().enumerate() {
+                    path.push(ErrorPathPart::Index(i as u32));
+                    self.mark(path, child, layer);
+                    path.pop();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Forgets the children of the param at `path`, when it's replaced
+    fn remove_children(&mut self, path: &[ErrorPathPart]) {
+        self.0.retain(|child_path, _| {
+            child_path.len() <= path.len() || !child_path.starts_with(path)
+        });
+    }
+}
+
+/// Applies `patch` over `base`. Structs and lists are merged, and any other
+/// param, or a param of a different type, is replaced by the patch.
+fn merge(
+    base: &mut ParamKind,
+    patch: &ParamKind,
+    layer: usize,
+    path: &mut Vec<ErrorPathPart>,
+    provenance: &mut Provenance,
+) {
+    match (base, patch) {
+        (ParamKind::Struct(base), ParamKind::Struct(patch)) => {
+            merge_struct(base, patch, layer, path, provenance)
+        }
+        (ParamKind::List(base), ParamKind::List(patch)) => {
+            provenance.0.insert(path.clone(), layer);
+            for (i, patch_child) in patch.0.iter().enumerate() {
+                path.push(ErrorPathPart::Index(i as u32));
+                match base.0.get_mut(i) {
+                    Some(base_child) => merge(base_child, patch_child, layer, path, provenance),
+                    None => {
+                        provenance.mark(path, patch_child, layer);
+                        base.0.push(patch_child.clone());
+                    }
+                }
+                path.pop();
+            }
+        }
+        (base, patch) => {
+            if matches!(base, ParamKind::Struct(_) | ParamKind::List(_)) {
+                provenance.remove_children(path);
+            }
+            provenance.mark(path, patch, layer);
+            *base = patch.clone();
+        }
+    }
+}
+
+/// Applies `patch` over `base`, merging children with the same hash
+fn merge_struct(
+    base: &mut ParamStruct,
+    patch: &ParamStruct,
+    layer: usize,
+    path: &mut Vec<ErrorPathPart>,
+    provenance: &mut Provenance,
+) {
+    provenance.0.insert(path.clone(), layer);
+    for (hash, patch_child) in &patch.0 {
+        path.push(ErrorPathPart::Hash(*hash));
+        match base.0.iter_mut().find(|(base_hash, _)| base_hash == hash) {
+            Some((_, base_child)) => merge(base_child, patch_child, layer, path, provenance),
+            None => {
+                provenance.mark(path, patch_child, layer);
+                base.0.push((*hash, patch_child.clone()));
+            }
+        }
+        path.pop();
+    }
+}
+
+/// Merges the patches over the base in order, so later patches take priority
+pub(super) fn merge_layers(base: &ParamStruct, patches: &[ParamStruct]) -> (ParamKind, Provenance) {
+    let mut merged = ParamKind::Struct(base.clone());
+    let mut provenance = Provenance::default();
+    let mut path = Vec::new();
+    provenance.mark(&mut path, &merged, 0);
+    if let ParamKind::Struct(merged) = &mut merged {
+        for (i, patch) in patches.iter().enumerate() {
+            merge_struct(merged, patch, i + 1, &mut path, &mut provenance);
+        }
+    }
+    (merged, provenance)
+}
+
+/// Reads the base file and patches, and converts the merged params. An error
+/// in the merged params is reported at the position of the param in the last
+/// layer which set it.
+pub(super) fn read_layers<T: Prc>(
+    base: &mut dyn ReadSeek,
+    patches: &mut [&mut dyn ReadSeek],
+) -> std::result::Result<Layered<T>, LayerError> {
+    let mut layers: Vec<&mut dyn ReadSeek> = Vec::with_capacity(patches.len() + 1);
+    layers.push(base);
+    layers.extend(
+        patches
+            .iter_mut()
+            .map(|patch| &mut **patch as &mut dyn ReadSeek),
+    );
+
+    let mut starts = Vec::with_capacity(layers.len());
+    let mut structs = Vec::with_capacity(layers.len());
+    for (layer, reader) in layers.iter_mut().enumerate() {
+        let read = reader
+            .stream_position()
+            .and_then(|start| Ok((start, crate::read_stream(reader)?)));
+        match read {
+            Ok((start, param_struct)) => {
+                starts.push(start);
+                structs.push(param_struct);
+            }
+            Err(e) => {
+                let error = Error::new(e, reader);
+                return Err(LayerError { layer, error });
+            }
+        }
+    }
+
+    let (merged, provenance) = merge_layers(&structs[0], &structs[1..]);
+    let mut error = match T::from_param(&merged) {
+        Ok(value) => return Ok(Layered { value, provenance }),
+        Err(error) => error,
+    };
+    // the deepest param on the path is the one some layer set
+    let (len, layer) = (0..=error.path.len())
+        .rev()
+        .find_map(|len| {
+            provenance
+                .layer(&error.path[..len])
+                .map(|layer| (len, layer))
+        })
+        .unwrap_or((0, 0));
+    let reader = &mut layers[layer];
+    let position = reader
+        .seek(SeekFrom::Start(starts[layer]))
+        .map_err(|e| Error::new(e, reader))
+        .and_then(|_| locate(reader, &error.path[..len]));
+    if let Ok(position) = position {
+        error.position = Ok(position);
+    }
+    Err(LayerError { layer, error })
+}
+
+/// Finds the position of the param at `path`, with the reader at the start
+/// of the file
+fn locate<R: Read + Seek>(reader: &mut R, path: &[ErrorPathPart]) -> Result<u64> {
+    let ctx = prepare(reader)?;
+    for part in path {
+        match part {
+            ErrorPathPart::Hash(hash) => {
+                StructData::from_stream(reader)?.search_child(reader, *hash, &ctx)?
+            }
+            ErrorPathPart::Index(index) => {
+                ListData::from_stream(reader)?.seek_child(reader, *index)?
+            }
+        }
+    }
+    reader.stream_position().map_err(|e| Error::new(e, reader))
+}
@@ -0,0 +1,131 @@
+// deferred decoding of params, for large subtrees which are rarely used
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Cursor};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use super::{
+    check_type, Error, ErrorKind, ParamNumber, ParamSource, ParamTree, Prc, PrcWrite, ReadContext,
+    Result,
+};
+use crate::param::ParamKind;
+
+/// A param which is only decoded when requested. Reading a `Lazy<T>` from a
+/// file keeps the bytes of the file, shared by every lazy param read with the
+/// same [ReadContext], so it can be decoded after the reader is gone. Params
+/// in memory are kept as they are.
+/// The type of the param is checked when it's read, but its contents are only
+/// checked when decoding. Errors from decoding have paths relative to this
+/// param.
+pub struct Lazy<T> {
+    source: LazySource,
+    ctx: ReadContext,
+    marker: PhantomData<fn() -> T>,
+}
+
+#[derive(Clone)]
+enum LazySource {
+    File { data: Arc<[u8]>, position: u64 },
+    Memory(ParamKind),
+}
+
+impl<T: Prc> Lazy<T> {
+    /// Decodes the param, with the game version it was read with
+    pub fn decode(&self) -> Result<T> {
+        match &self.source {
+            LazySource::File { data, position } => {
+                let mut reader = Cursor::new(&data[..]);
+                reader.set_position(*position);
+                T::read_param(&mut reader, &self.ctx)
+            }
+            LazySource::Memory(param) => T::read_param(&mut ParamTree::new(param), &self.ctx),
+        }
+    }
+}
+
+impl<T: Prc> Prc for Lazy<T> {
+    fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        let node = source.node()?;
+        match T::param_number() {
+            Some(expected) => check_type(source, expected)?,
+            // any type is accepted, but it still has to be a param
+            None => {
+                let received = source.peek_number()?;
+                if ParamNumber::try_from(received).is_err() {
+                    return Err(Error::new_with_pos(
+                        ErrorKind::InvalidData(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid param number {}", received),
+                        )),
+                        source.position(node),
+                    ));
+                }
+            }
+        }
+        let lazy_source = match source.file_data(node, ctx)? {
+            Some((data, position)) => LazySource::File { data, position },
+            None => LazySource::Memory(source.read_dynamic(ctx)?),
+        };
+        Ok(Lazy {
+            source: lazy_source,
+            ctx: ctx.clone(),
+            marker: PhantomData,
+        })
+    }
+
+    fn param_number() -> Option<ParamNumber> {
+        T::param_number()
+    }
+}
+
+impl<T> PrcWrite for Lazy<T> {
+    /// Writes the param as it was read, without converting it to T
+    fn to_param(&self) -> std::result::Result<ParamKind, io::Error> {
+        match &self.source {
+            LazySource::File { data, position } => {
+                let mut reader = Cursor::new(&data[..]);
+                reader.set_position(*position);
+                ParamKind::read_param(&mut reader, &self.ctx)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            LazySource::Memory(param) => Ok(param.clone()),
+        }
+    }
+}
+
+// implemented manually, so T doesn't need to implement these
+
+impl<T> Clone for Lazy<T> {
+    fn clone(&self) -> Self {
+        Lazy {
+            source: self.source.clone(),
+            ctx: self.ctx.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Lazy")
+            .field("source", &self.source)
+            .field("ctx", &self.ctx)
+            .finish()
+    }
+}
+
+// the file is large, so only its size is shown
+impl fmt::Debug for LazySource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LazySource::File { data, position } => f
+                .debug_struct("File")
+                .field("data", &data.len())
+                .field("position", position)
+                .finish(),
+            LazySource::Memory(param) => f.debug_tuple("Memory").field(param).finish(),
+        }
+    }
+}
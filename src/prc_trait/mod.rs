@@ -1,38 +1,292 @@
-use std::cmp::Ordering;
+mod borrow;
+mod coerce;
+mod containers;
+mod dynamic;
+mod keyed;
+mod layered;
+mod lazy;
+mod source;
+mod validate;
+mod version;
+mod write;
+
+pub use borrow::{BorrowedIter, BorrowedList, PrcBorrow};
+pub use coerce::{Coerce, Coercible};
+pub use keyed::{keyed_list_to_param, read_keyed_list, read_keyed_list_diagnostic};
+pub use layered::{LayerError, Layered, Provenance, ReadSeek};
+pub use lazy::Lazy;
+#[cfg(feature = "xml-feat")]
+pub use source::Xml;
+pub use source::{ParamInput, ParamSource, ParamTree};
+pub use validate::{check_len, check_range, check_valid, invalid_child};
+pub use version::{
+    require_for_version, require_for_version_diagnostic, ParseVersionError, Version,
+};
+pub use write::PrcWrite;
+
 use std::convert::TryFrom;
+use std::fmt;
 use std::io::{Read, Seek, SeekFrom};
+use std::sync::{Arc, OnceLock};
 
 use byteorder::{LittleEndian, ReadBytesExt};
 use hash40::{Hash40, ReadHash40};
 
+use crate::param::{ParamKind, ParamStruct, Placeholder};
+
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncReadExt};
+
 /// A trait allowing a type to be converted from the param container format
 pub trait Prc: Sized {
-    /// Creates Self by reading the from the data. The reader should be
-    /// positioned at the start of the param marker before calling this
-    fn read_param<R: Read + Seek>(reader: &mut R, offsets: FileOffsets) -> Result<Self>;
+    /// Creates Self by reading the param the source is at, such as the
+    /// param at the reader's position in a binary file
+    fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self>;
 
     /// Controls how structs should try to behave when reading the param.
     /// Implementing this manually is usually not necessary, but it can
     /// be useful in certain cases. For example, [Option] types work by
     /// returning [None] when the hash isn't found in the struct.
-    fn read_from_struct<R: Read + Seek>(
-        reader: &mut R,
+    fn read_from_struct<S: ParamSource>(
+        source: &mut S,
         hash: Hash40,
-        offsets: FileOffsets,
-        struct_data: StructData,
+        ctx: &ReadContext,
+        struct_data: StructData<S::Node>,
     ) -> Result<Self> {
-        struct_data.read_child(reader, hash, offsets)
+        struct_data.read_child(source, hash, ctx)
     }
 
     /// A blanket implementation which reads the entire file to create
     /// Self. The reader should be at the beginning of the file before
     /// calling this.
     fn read_file<R: Read + Seek>(reader: &mut R) -> Result<Self> {
-        let offsets = prepare(reader)?;
-        Self::read_param(reader, offsets)
+        let ctx = prepare(reader)?;
+        Self::read_param(reader, &ctx)
+    }
+
+    /// Creates Self from a param which is already in memory, such as one
+    /// returned from [open](crate::open) or read from XML. The param is read
+    /// through a [ParamTree], so errors report the same path as
+    /// [Prc::read_param], but have no position.
+    fn from_param(param: &ParamKind) -> Result<Self> {
+        Self::read_param(&mut ParamTree::new(param), &ReadContext::default())
+    }
+
+    /// Like [Prc::read_file], but reads fields with the `since`, `until` and
+    /// `if` attributes for the game version of the file
+    fn read_file_versioned<R: Read + Seek>(reader: &mut R, version: Version) -> Result<Self> {
+        let ctx = prepare(reader)?.with_version(version);
+        Self::read_param(reader, &ctx)
+    }
+
+    /// Like [Prc::from_param], but converts fields with the `since`, `until`
+    /// and `if` attributes for the game version
+    fn from_param_versioned(param: &ParamKind, version: Version) -> Result<Self> {
+        Self::read_param(
+            &mut ParamTree::new(param),
+            &ReadContext::default().with_version(version),
+        )
+    }
+
+    /// The value used in place of a param which couldn't be read in
+    /// diagnostic mode, or [None] if the type has no sensible default.
+    fn fallback() -> Option<Self> {
+        None
+    }
+
+    /// The type of param Self is read from, or [None] if it can be read from
+    /// several types. Lets [Lazy] check the type of a param before it's
+    /// decoded.
+    fn param_number() -> Option<ParamNumber> {
+        None
+    }
+
+    /// Reads the param like [Prc::read_param], but records errors instead
+    /// of stopping at them. Returns [None] if Self couldn't be created, even
+    /// with fallback values. The default implementation records the first
+    /// error and uses [Prc::fallback], so only types with children need to
+    /// implement this.
+    fn read_param_diagnostic<S: ParamSource>(
+        source: &mut S,
+        ctx: &ReadContext,
+        errors: &mut Vec<Error>,
+    ) -> Option<Self> {
+        match Self::read_param(source, ctx) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                errors.push(e);
+                Self::fallback()
+            }
+        }
+    }
+
+    /// The diagnostic counterpart to [Prc::read_from_struct]. Missing params
+    /// are recorded, unless [Prc::read_from_struct] accepts them.
+    fn read_from_struct_diagnostic<S: ParamSource>(
+        source: &mut S,
+        hash: Hash40,
+        ctx: &ReadContext,
+        struct_data: StructData<S::Node>,
+        errors: &mut Vec<Error>,
+    ) -> Option<Self> {
+        match struct_data.search_child(source, hash, ctx) {
+            Ok(()) => diagnose_child(ErrorPathPart::Hash(hash), errors, |errors| {
+                Self::read_param_diagnostic(source, ctx, errors)
+            }),
+            // types like Option are fine with the param missing
+            Err(e) => match Self::read_from_struct(source, hash, ctx, struct_data) {
+                Ok(value) => Some(value),
+                Err(_) => {
+                    errors.push(e);
+                    Self::fallback()
+                }
+            },
+        }
+    }
+
+    /// Reads the entire file like [Prc::read_file], but keeps going after
+    /// errors to find as many problems as possible at once. Params which
+    /// failed to read are filled in using [Prc::fallback] where possible.
+    fn read_file_diagnostic<R: Read + Seek>(reader: &mut R) -> Diagnosed<Self> {
+        let mut errors = Vec::new();
+        let value = match prepare(reader) {
+            Ok(ctx) => Self::read_param_diagnostic(reader, &ctx, &mut errors),
+            Err(e) => {
+                errors.push(e);
+                None
+            }
+        };
+        Diagnosed { value, errors }
+    }
+
+    /// Reads the base file with each patch file layered over it, so every
+    /// param comes from the last patch which has it. Struct children are
+    /// merged by hash, and list children by index. The [Provenance] of the
+    /// result records which file set each param. The files can use different
+    /// types of readers, and each error has the layer of the file it was
+    /// found in.
+    fn read_layered(
+        base: &mut dyn ReadSeek,
+        patches: &mut [&mut dyn ReadSeek],
+    ) -> std::result::Result<Layered<Self>, LayerError> {
+        layered::read_layers(base, patches)
+    }
+
+    /// Reads Self from any [ParamInput], such as a binary file, a param in
+    /// memory, or an XML document
+    fn read_source<I: ParamInput>(input: I) -> Result<Self> {
+        input.read_prc()
+    }
+
+    /// Like [Prc::read_source], but reads fields with the `since`, `until`
+    /// and `if` attributes for the game version
+    fn read_source_versioned<I: ParamInput>(input: I, version: Version) -> Result<Self> {
+        input.read_root(Some(version))
+    }
+
+    /// The in-memory counterpart to [Prc::read_layered]
+    fn from_layers(base: &ParamStruct, patches: &[ParamStruct]) -> Result<Layered<Self>> {
+        let (merged, provenance) = layered::merge_layers(base, patches);
+        let value = Self::from_param(&merged)?;
+        Ok(Layered { value, provenance })
+    }
+}
+
+/// The result of a diagnostic read
+#[derive(Debug)]
+pub struct Diagnosed<T> {
+    /// The value read, with fallback values for params which had errors.
+    /// [None] if some param had no fallback value.
+    pub value: Option<T>,
+    /// Every error found, in the order they were encountered
+    pub errors: Vec<Error>,
+}
+
+/// Runs a diagnostic read of a child param, adding `part` to the path of
+/// each error it records
+fn diagnose_child<T>(
+    part: ErrorPathPart,
+    errors: &mut Vec<Error>,
+    read: impl FnOnce(&mut Vec<Error>) -> Option<T>,
+) -> Option<T> {
+    let start = errors.len();
+    let value = read(errors);
+    for e in &mut errors[start..] {
+        e.path.insert(0, part);
+    }
+    value
+}
+
+/// The diagnostic counterpart to [default_if_missing]. If the only error
+/// recorded by `read` is that the param is missing, the error is dropped and
+/// the default value is used.
+pub fn default_if_missing_diagnostic<T>(
+    hash: Hash40,
+    errors: &mut Vec<Error>,
+    read: impl FnOnce(&mut Vec<Error>) -> Option<T>,
+    default: impl FnOnce() -> T,
+) -> Option<T> {
+    let start = errors.len();
+    let value = read(errors);
+    match &errors[start..] {
+        [e] if e.path.is_empty()
+            && matches!(e.kind, ErrorKind::ParamNotFound(missing) if missing == hash) =>
+        {
+            errors.pop();
+            Some(default())
+        }
+        _ => value,
     }
 }
 
+/// Replaces the error from a child param which isn't in the struct with a
+/// default value. Errors from inside the child are kept. Used by the derive
+/// macro for fields with the `#[prc(default)]` attribute.
+pub fn default_if_missing<T>(
+    result: Result<T>,
+    hash: Hash40,
+    default: impl FnOnce() -> T,
+) -> Result<T> {
+    match result {
+        Err(e)
+            if e.path.is_empty()
+                && matches!(e.kind, ErrorKind::ParamNotFound(missing) if missing == hash) =>
+        {
+            Ok(default())
+        }
+        result => result,
+    }
+}
+
+/// Reads the param as T and converts it to U, reporting a failed conversion
+/// as [ErrorKind::Conversion]. Used by the derive macro for fields with the
+/// `#[prc(from)]` and `#[prc(try_from)]` attributes.
+pub fn read_try_from<S, T, U>(source: &mut S, ctx: &ReadContext) -> Result<U>
+where
+    S: ParamSource,
+    T: Prc,
+    U: TryFrom<T>,
+    U::Error: fmt::Display,
+{
+    let node = source.node()?;
+    let value = T::read_param(source, ctx)?;
+    U::try_from(value).map_err(|e| {
+        Error::new_with_pos(ErrorKind::Conversion(e.to_string()), source.position(node))
+    })
+}
+
+/// Reads the entire file from an async reader to create T. The file is
+/// buffered into memory first, since reading params requires seeking,
+/// then read with [Prc::read_file].
+#[cfg(feature = "async")]
+pub async fn read_file_async<T: Prc, R: AsyncRead + Unpin>(reader: &mut R) -> Result<T> {
+    let mut buf = Vec::new();
+    if let Err(e) = reader.read_to_end(&mut buf).await {
+        return Err(Error::new_with_pos(e, Ok(buf.len() as u64)));
+    }
+    T::read_file(&mut std::io::Cursor::new(buf))
+}
+
 // make custom reader struct to return errors in the correct type?
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -49,14 +303,66 @@ pub struct Error {
 /// The original error thrown
 #[derive(Debug)]
 pub enum ErrorKind {
-    WrongParamNumber { expected: ParamNumber, received: u8 },
+    WrongParamNumber {
+        expected: ParamNumber,
+        received: u8,
+    },
     ParamNotFound(Hash40),
+    /// A param which is required for the game version being read
+    MissingForVersion {
+        hash: Hash40,
+        version: Version,
+    },
+    WrongListLength {
+        expected: u32,
+        received: u32,
+    },
+    /// A value which doesn't match any variant of an enum
+    UnknownValue(ParamKind),
+    /// A param in a struct which doesn't match any of the fields
+    UnknownParam(Hash40),
+    /// A value which couldn't be converted to the type of its field,
+    /// with the message of the conversion error
+    Conversion(String),
+    /// A key in a keyed list which was already used by the struct at
+    /// `first_index`
+    DuplicateKey {
+        key: Hash40,
+        first_index: u32,
+    },
+    /// A value outside of the range of its field's `range` attribute, with
+    /// both formatted for display
+    NotInRange {
+        value: String,
+        range: String,
+    },
+    /// A value with a different length than its field's `len` attribute
+    InvalidLength {
+        expected: usize,
+        received: usize,
+    },
+    /// A value rejected by its field's `validate` function, with the message
+    /// of the validation error
+    Validation(String),
+    /// An integer which doesn't fit the type it was coerced to
+    OutOfRange {
+        value: i64,
+        expected: ParamNumber,
+    },
+    /// The data ended before the param was read, usually from a truncated file
+    UnexpectedEof(std::io::Error),
+    /// The data can't be decoded, such as an out of range hash index
+    InvalidData(std::io::Error),
+    /// Any other error from the reader
     Io(std::io::Error),
+    /// An XML document which couldn't be parsed into params
+    #[cfg(feature = "xml-feat")]
+    Xml(crate::xml::ReadError),
 }
 
 /// Used for the path of an error. Could be a hash (for structs) or
 /// an index (for a list)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ErrorPathPart {
     Index(u32),
     Hash(Hash40),
@@ -64,18 +370,228 @@ pub enum ErrorPathPart {
 
 /// Offsets to tables derived from the file header, necessary when reading
 /// certain params
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Default, Copy, Clone)]
 pub struct FileOffsets {
     pub hashes: u64,
     pub ref_table: u64,
 }
 
-/// Information read from a struct to facilitate reading child params
+/// The tables of a file, loaded once so reading params doesn't need to seek
+/// into them. Created by [prepare] and passed to [Prc::read_param]. Cloning
+/// is cheap, since the tables are shared. The default context has no file,
+/// for reading params in memory with a [ParamTree].
+#[derive(Clone, Default)]
+pub struct ReadContext {
+    offsets: FileOffsets,
+    hashes: Arc<[Hash40]>,
+    // the ref table and strings, if they were loaded. Otherwise they're read from the file
+    ref_table: Option<Arc<[u8]>>,
+    // the game version of the file, for fields which depend on it
+    version: Option<Version>,
+    // the bytes of the whole file, read once for the first lazy param
+    file: Arc<OnceLock<Arc<[u8]>>>,
+}
+
+impl ReadContext {
+    /// Reads the header and hash table of the file, and also the ref table
+    /// if `load_ref_table` is true. The reader should be at the beginning of
+    /// the file, and is moved to the start of the params.
+    pub fn from_header<R: Read + Seek>(reader: &mut R, load_ref_table: bool) -> Result<Self> {
+        Self::from_header_internal(reader, load_ref_table).map_err(|e| Error::new(e, reader))
+    }
+
+    fn from_header_internal<R: Read + Seek>(
+        reader: &mut R,
+        load_ref_table: bool,
+    ) -> std::io::Result<Self> {
+        reader.seek(SeekFrom::Current(8))?;
+        let hashes_size = reader.read_u32::<LittleEndian>()?;
+        let ref_table_size = reader.read_u32::<LittleEndian>()?;
+
+        let hashes = reader.stream_position()?;
+        let hash_table = read_table(reader, hashes_size as u64)?
+            .chunks_exact(8)
+            .map(|mut hash| hash.read_hash40::<LittleEndian>())
+            .collect::<std::io::Result<Vec<_>>>()?;
+
+        let ref_table = reader.stream_position()?;
+        let ref_table_data = if load_ref_table {
+            Some(read_table(reader, ref_table_size as u64)?.into())
+        } else {
+            reader.seek(SeekFrom::Current(ref_table_size as i64))?;
+            None
+        };
+
+        Ok(ReadContext {
+            offsets: FileOffsets { hashes, ref_table },
+            hashes: hash_table.into(),
+            ref_table: ref_table_data,
+            version: None,
+            file: Arc::default(),
+        })
+    }
+
+    /// Sets the game version of the file, so fields with the `since`,
+    /// `until` and `if` attributes are read for that version
+    pub fn with_version(mut self, version: Version) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    /// The game version of the file, if one was set
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    /// The positions of the tables in the file
+    pub fn offsets(&self) -> FileOffsets {
+        self.offsets
+    }
+
+    /// The hashes of the file, in the order of the hash table
+    pub fn hash_table(&self) -> &[Hash40] {
+        &self.hashes
+    }
+
+    /// Looks up a hash by its index in the hash table
+    #[inline]
+    pub fn hash(&self, index: u32) -> std::io::Result<Hash40> {
+        self.hashes.get(index as usize).copied().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("hash index {} is outside of the hash table", index),
+            )
+        })
+    }
+
+    /// The bytes of the whole file, which are read from the reader the
+    /// first time they're needed and shared by every clone of the context.
+    /// The reader is left where it was.
+    pub(crate) fn file_data<R: Read + Seek>(&self, reader: &mut R) -> std::io::Result<Arc<[u8]>> {
+        if let Some(data) = self.file.get() {
+            return Ok(data.clone());
+        }
+        let position = reader.stream_position()?;
+        reader.seek(SeekFrom::Start(0))?;
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        reader.seek(SeekFrom::Start(position))?;
+        Ok(self.file.get_or_init(|| data.into()).clone())
+    }
+
+    /// Reads the entry of a struct's ref table at the given offset into the
+    /// ref table. Returns the index of the hash and the offset of the param.
+    fn ref_entry<R: Read + Seek>(
+        &self,
+        reader: &mut R,
+        offset: u64,
+    ) -> std::io::Result<(u32, u32)> {
+        match &self.ref_table {
+            Some(table) => {
+                let entry = table_slice(table, offset, 8)?;
+                Ok((
+                    u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]),
+                    u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]),
+                ))
+            }
+            None => {
+                reader.seek(SeekFrom::Start(self.offsets.ref_table + offset))?;
+                Ok((
+                    reader.read_u32::<LittleEndian>()?,
+                    reader.read_u32::<LittleEndian>()?,
+                ))
+            }
+        }
+    }
+
+    /// Reads the null-terminated string at the given offset into the ref
+    /// table, decoded like [read_stream](crate::read_stream) does. The reader
+    /// is left where it was.
+    fn string<R: Read + Seek>(&self, reader: &mut R, offset: u32) -> std::io::Result<String> {
+        let bytes = match &self.ref_table {
+            Some(table) => {
+                let bytes = table_slice(table, offset as u64, 0)?;
+                let len = bytes.iter().position(|byte| *byte == 0).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::UnexpectedEof,
+                        "string isn't terminated before the end of the ref table",
+                    )
+                })?;
+                bytes[..len].to_vec()
+            }
+            None => {
+                let mut bytes = Vec::new();
+                let end_position = reader.stream_position()?;
+                reader.seek(SeekFrom::Start(self.offsets.ref_table + offset as u64))?;
+                loop {
+                    let byte = reader.read_u8()?;
+                    if byte == 0 {
+                        break;
+                    }
+                    bytes.push(byte);
+                }
+                reader.seek(SeekFrom::Start(end_position))?;
+                bytes
+            }
+        };
+        Ok(crate::disasm::decode_string(bytes))
+    }
+}
+
+// the tables are large, so only their sizes are shown
+impl fmt::Debug for ReadContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReadContext")
+            .field("offsets", &self.offsets)
+            .field("hashes", &self.hashes.len())
+            .field(
+                "ref_table",
+                &self.ref_table.as_ref().map(|table| table.len()),
+            )
+            .field("version", &self.version)
+            .finish()
+    }
+}
+
+/// Reads a table of the given size, without trusting the size for the allocation
+fn read_table<R: Read>(reader: &mut R, size: u64) -> std::io::Result<Vec<u8>> {
+    let mut table = Vec::new();
+    reader.take(size).read_to_end(&mut table)?;
+    if (table.len() as u64) < size {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(table)
+}
+
+/// The bytes of a loaded table from `offset`, which must have at least `len` bytes
+#[inline]
+fn table_slice(table: &[u8], offset: u64, len: u64) -> std::io::Result<&[u8]> {
+    usize::try_from(offset)
+        .ok()
+        .and_then(|offset| table.get(offset..))
+        .filter(|bytes| bytes.len() as u64 >= len)
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("offset {} is outside of the ref table", offset),
+            )
+        })
+}
+
+/// Information read from a list to facilitate reading child params. `node`
+/// is the list in its [ParamSource], such as its position in a binary file.
 #[derive(Debug, Copy, Clone)]
-pub struct StructData {
-    pub position: u64,
+pub struct ListData<N = u64> {
+    pub node: N,
+    pub len: u32,
+}
+
+/// Information read from a struct to facilitate reading child params. `node`
+/// is the struct in its [ParamSource], such as its position in a binary file.
+#[derive(Debug, Copy, Clone)]
+pub struct StructData<N = u64> {
+    pub node: N,
     pub len: u32,
-    pub ref_offset: u32,
 }
 
 /// The number associated with each type of param in a file
@@ -96,9 +612,29 @@ pub enum ParamNumber {
     Struct,
 }
 
-pub fn check_type<R: Read + Seek>(reader: &mut R, value: ParamNumber) -> Result<()> {
-    let pre_pos = reader.stream_position();
-    let read = reader.read_u8().map_err(|e| Error::new(e, reader))?;
+/// The error for a param in memory which isn't the expected type
+fn wrong_param(expected: ParamNumber, param: &ParamKind) -> Error {
+    match ParamNumber::try_from(param) {
+        Ok(received) => Error::new_in_memory(ErrorKind::WrongParamNumber {
+            expected,
+            received: received.into(),
+        }),
+        // report why the param couldn't be decoded, where it was in the file
+        Err(placeholder) => Error::new_with_pos(
+            ErrorKind::InvalidData(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                placeholder.error.clone(),
+            )),
+            Ok(placeholder.position),
+        ),
+    }
+}
+
+/// Returns an error unless the param the source is at has the expected
+/// type. The source isn't moved.
+pub fn check_type<S: ParamSource>(source: &mut S, value: ParamNumber) -> Result<()> {
+    let node = source.node()?;
+    let read = source.peek_number()?;
 
     if read != value.into() {
         Err(Error::new_with_pos(
@@ -106,293 +642,383 @@ pub fn check_type<R: Read + Seek>(reader: &mut R, value: ParamNumber) -> Result<
                 expected: value,
                 received: read,
             },
-            pre_pos,
+            source.position(node),
         ))
     } else {
         Ok(())
     }
 }
 
-impl StructData {
-    pub fn from_stream<R: Read + Seek>(reader: &mut R) -> Result<Self> {
-        let position = reader
-            .seek(SeekFrom::Current(0))
-            .map_err(|e| Error::new(e, reader))?;
-
-        check_type(reader, ParamNumber::Struct)?;
+impl<N: Copy> StructData<N> {
+    /// Reads the struct the source is at
+    pub fn from_stream<S: ParamSource<Node = N>>(source: &mut S) -> Result<Self> {
+        source.read_struct()
+    }
 
-        let len = reader
-            .read_u32::<LittleEndian>()
-            .map_err(|e| Error::new(e, reader))?;
-        let ref_offset = reader
-            .read_u32::<LittleEndian>()
-            .map_err(|e| Error::new(e, reader))?;
+    /// Moves the source to the child param with the provided hash
+    fn search_child<S: ParamSource<Node = N>>(
+        &self,
+        source: &mut S,
+        hash: Hash40,
+        ctx: &ReadContext,
+    ) -> Result<()> {
+        match source.find_child(self, hash, ctx)? {
+            Some(node) => source.seek_node(node),
+            None => Err(Error::new_with_pos(
+                ErrorKind::ParamNotFound(hash),
+                source.position(self.node),
+            )),
+        }
+    }
 
-        reader
-            .seek(SeekFrom::Start(position))
-            .map_err(|e| Error::new(e, reader))?;
+    /// Returns the first of `hashes` which is a child param of the struct, or
+    /// the first hash if none are, so reading it reports the first hash as
+    /// missing. Used by the derive macro for fields with the
+    /// `#[prc(alias = "...")]` attribute.
+    pub fn find_hash<S: ParamSource<Node = N>>(
+        &self,
+        source: &mut S,
+        hashes: &[Hash40],
+        ctx: &ReadContext,
+    ) -> Hash40 {
+        hashes
+            .iter()
+            .copied()
+            .find(|hash| matches!(source.find_child(self, *hash, ctx), Ok(Some(_))))
+            .unwrap_or(hashes[0])
+    }
 
-        Ok(Self {
-            position,
-            len,
-            ref_offset,
-        })
+    /// Reads every child param whose hash isn't in `known`, in the same order
+    /// as [read_stream](crate::read_stream). Used by the derive macro for
+    /// fields with the `#[prc(rest)]` attribute.
+    pub fn read_unknown<S: ParamSource<Node = N>>(
+        &self,
+        source: &mut S,
+        ctx: &ReadContext,
+        known: &[Hash40],
+    ) -> Result<ParamStruct> {
+        self.entries(source, ctx)?
+            .into_iter()
+            .filter(|(hash, _)| !known.contains(hash))
+            .map(|(hash, node)| {
+                source.seek_node(node)?;
+                let param = ParamKind::read_param(source, ctx).map_err(|mut e| {
+                    e.path.insert(0, ErrorPathPart::Hash(hash));
+                    e
+                })?;
+                Ok((hash, param))
+            })
+            .collect::<Result<_>>()
+            .map(ParamStruct)
     }
 
-    /// Moves the reader to the child param with the provided hash
-    fn search_child<R: Read + Seek>(
+    /// Returns an error for the first child param whose hash isn't in
+    /// `known`. Used by the derive macro for `#[prc(deny_unknown_fields)]`.
+    pub fn deny_unknown<S: ParamSource<Node = N>>(
         &self,
-        reader: &mut R,
-        hash: Hash40,
-        offsets: FileOffsets,
+        source: &mut S,
+        ctx: &ReadContext,
+        known: &[Hash40],
     ) -> Result<()> {
-        let mut low = 0;
-        // on a zero length vec, high = -1, which ends the loop
-        let mut high = self.len as i64 - 1;
-        while low <= high {
-            let i = (low + high) / 2;
-            reader
-                .seek(SeekFrom::Start(
-                    offsets.ref_table + self.ref_offset as u64 + (i as u64 * 8),
-                ))
-                .map_err(|e| Error::new(e, reader))?;
-
-            let hash_index = reader
-                .read_u32::<LittleEndian>()
-                .map_err(|e| Error::new(e, reader))?;
-            let param_offset = reader
-                .read_u32::<LittleEndian>()
-                .map_err(|e| Error::new(e, reader))?;
-
-            reader
-                .seek(SeekFrom::Start(offsets.hashes + (hash_index as u64 * 8)))
-                .map_err(|e| Error::new(e, reader))?;
-            let read_hash = reader
-                .read_hash40::<LittleEndian>()
-                .map_err(|e| Error::new(e, reader))?;
-
-            match read_hash.cmp(&hash) {
-                Ordering::Less => low = i + 1,
-                Ordering::Greater => high = i - 1,
-                Ordering::Equal => {
-                    reader
-                        .seek(SeekFrom::Start(self.position + (param_offset as u64)))
-                        .map_err(|e| Error::new(e, reader))?;
-                    return Ok(());
-                }
-            }
+        match self
+            .entries(source, ctx)?
+            .into_iter()
+            .find(|(hash, _)| !known.contains(hash))
+        {
+            Some((hash, node)) => Err(Error::new_with_pos(
+                ErrorKind::UnknownParam(hash),
+                source.position(node),
+            )),
+            None => Ok(()),
         }
-        Err(Error::new_with_pos(
-            ErrorKind::ParamNotFound(hash),
-            Ok(self.position),
-        ))
     }
 
-    /// Moves the reader to the child param with the provided hash and reads
+    /// The diagnostic counterpart to [StructData::deny_unknown], which
+    /// records an error for every unknown child param
+    pub fn deny_unknown_diagnostic<S: ParamSource<Node = N>>(
+        &self,
+        source: &mut S,
+        ctx: &ReadContext,
+        known: &[Hash40],
+        errors: &mut Vec<Error>,
+    ) {
+        match self.entries(source, ctx) {
+            Ok(entries) => errors.extend(
+                entries
+                    .into_iter()
+                    .filter(|(hash, _)| !known.contains(hash))
+                    .map(|(hash, node)| {
+                        Error::new_with_pos(ErrorKind::UnknownParam(hash), source.position(node))
+                    }),
+            ),
+            Err(e) => errors.push(e),
+        }
+    }
+
+    /// Returns the hash and node of every child param, in the same order as
+    /// [read_stream](crate::read_stream)
+    pub fn entries<S: ParamSource<Node = N>>(
+        &self,
+        source: &mut S,
+        ctx: &ReadContext,
+    ) -> Result<Vec<(Hash40, N)>> {
+        source.children(self, ctx)
+    }
+
+    /// Moves the source to the child param with the provided hash and reads
     /// the param fulfilling the [Prc] trait.
-    pub fn read_child<R: Read + Seek, T: Prc>(
+    pub fn read_child<S: ParamSource<Node = N>, T: Prc>(
         &self,
-        reader: &mut R,
+        source: &mut S,
         hash: Hash40,
-        offsets: FileOffsets,
+        ctx: &ReadContext,
+    ) -> Result<T> {
+        self.read_child_with(source, hash, ctx, T::read_param)
+    }
+
+    /// Moves the source to the child param with the provided hash and reads
+    /// it with `read`, adding the hash to the path of any error
+    pub fn read_child_with<S: ParamSource<Node = N>, T>(
+        &self,
+        source: &mut S,
+        hash: Hash40,
+        ctx: &ReadContext,
+        read: impl FnOnce(&mut S, &ReadContext) -> Result<T>,
     ) -> Result<T> {
         // If the child param isn't found, we don't push that hash into the error path
-        self.search_child(reader, hash, offsets)?;
+        self.search_child(source, hash, ctx)?;
 
         // Errors caused while doing anything else will add the hash to the path
-        T::read_param(reader, offsets).map_err(|mut e| {
+        read(source, ctx).map_err(|mut e| {
             e.path.insert(0, ErrorPathPart::Hash(hash));
-            Error {
-                path: e.path,
-                position: e.position,
-                kind: e.kind,
-            }
+            e
         })
     }
-}
 
-/// Reads the header data and moves the reader to the start of the params
-pub fn prepare<R: Read + Seek>(reader: &mut R) -> Result<FileOffsets> {
-    prepare_internal(reader).map_err(|e| Error::new(e, reader))
+    /// The diagnostic counterpart to [StructData::read_child_with]. A missing
+    /// child is recorded as an error.
+    pub fn read_child_diagnostic_with<S: ParamSource<Node = N>, T>(
+        &self,
+        source: &mut S,
+        hash: Hash40,
+        ctx: &ReadContext,
+        errors: &mut Vec<Error>,
+        read: impl FnOnce(&mut S, &ReadContext, &mut Vec<Error>) -> Option<T>,
+    ) -> Option<T> {
+        match self.search_child(source, hash, ctx) {
+            Ok(()) => diagnose_child(ErrorPathPart::Hash(hash), errors, |errors| {
+                read(source, ctx, errors)
+            }),
+            Err(e) => {
+                errors.push(e);
+                None
+            }
+        }
+    }
 }
 
-fn prepare_internal<R: Read + Seek>(reader: &mut R) -> std::io::Result<FileOffsets> {
-    reader.seek(SeekFrom::Current(8))?;
-    let hashes_size = reader.read_u32::<LittleEndian>()?;
-    let ref_table_size = reader.read_u32::<LittleEndian>()?;
-
-    let hashes = reader.seek(SeekFrom::Current(0))?;
+impl<N: Copy> ListData<N> {
+    /// Reads the list the source is at
+    pub fn from_stream<S: ParamSource<Node = N>>(source: &mut S) -> Result<Self> {
+        source.read_list()
+    }
 
-    reader.seek(SeekFrom::Current(hashes_size as i64))?;
-    let ref_table = reader.seek(SeekFrom::Current(0))?;
+    /// Returns an error unless the list has the expected length
+    pub fn check_len<S: ParamSource<Node = N>>(&self, source: &S, expected: u32) -> Result<()> {
+        if self.len != expected {
+            Err(Error::new_with_pos(
+                ErrorKind::WrongListLength {
+                    expected,
+                    received: self.len,
+                },
+                source.position(self.node),
+            ))
+        } else {
+            Ok(())
+        }
+    }
 
-    reader.seek(SeekFrom::Current(ref_table_size as i64))?;
-    Ok(FileOffsets { hashes, ref_table })
-}
+    /// Moves the source to the child param at the provided index and reads
+    /// the param fulfilling the [Prc] trait.
+    pub fn read_child<S: ParamSource<Node = N>, T: Prc>(
+        &self,
+        source: &mut S,
+        index: u32,
+        ctx: &ReadContext,
+    ) -> Result<T> {
+        self.seek_child(source, index)?;
 
-// basic implementations for all types except struct here
+        // read the type, and potentially add index to the error path
+        T::read_param(source, ctx).map_err(|mut e| {
+            e.path.insert(0, ErrorPathPart::Index(index));
+            e
+        })
+    }
 
-impl Prc for bool {
-    fn read_param<R: Read + Seek>(reader: &mut R, _offsets: FileOffsets) -> Result<Self> {
-        check_type(reader, ParamNumber::Bool)?;
-        reader
-            .read_u8()
-            .map_err(|e| Error::new(e, reader))
-            .map(|byte| byte > 0)
+    /// Moves the source to the child param at the provided index
+    fn seek_child<S: ParamSource<Node = N>>(&self, source: &mut S, index: u32) -> Result<()> {
+        let node = source.list_child(self, index)?;
+        source.seek_node(node)
     }
-}
 
-macro_rules! impl_read_byte {
-    ($(($param_type:ty, $num:path, $read_func:ident)),*) => {
-        $(
-            impl Prc for $param_type {
-                fn read_param<R: Read + Seek>(reader: &mut R, _offsets: FileOffsets) -> Result<Self> {
-                    check_type(reader, $num)?;
-                    ReadBytesExt::$read_func(reader).map_err(|e| Error::new(e, reader))
+    /// The diagnostic counterpart to [ListData::read_child]
+    pub fn read_child_diagnostic<S: ParamSource<Node = N>, T: Prc>(
+        &self,
+        source: &mut S,
+        index: u32,
+        ctx: &ReadContext,
+        errors: &mut Vec<Error>,
+    ) -> Option<T> {
+        diagnose_child(ErrorPathPart::Index(index), errors, |errors| {
+            match self.seek_child(source, index) {
+                Ok(()) => T::read_param_diagnostic(source, ctx, errors),
+                Err(e) => {
+                    errors.push(e);
+                    T::fallback()
                 }
             }
-        )*
-    };
+        })
+    }
 }
 
+/// Reads the header data and tables, and moves the reader to the start of
+/// the params. Both tables are loaded, see [ReadContext::from_header] to
+/// only load the hash table.
+pub fn prepare<R: Read + Seek>(reader: &mut R) -> Result<ReadContext> {
+    ReadContext::from_header(reader, true)
+}
+
+// basic implementations for all types except struct here
+
 macro_rules! impl_read_value {
-    ($(($param_type:ty, $num:path, $read_func:ident)),*) => {
+    ($(($param_type:ty, $num:path, $kind:path)),*) => {
         $(
             impl Prc for $param_type {
-                fn read_param<R: Read + Seek>(reader: &mut R, _offsets: FileOffsets) -> Result<Self> {
-                    check_type(reader, $num)?;
-                    ReadBytesExt::$read_func::<LittleEndian>(reader).map_err(|e| Error::new(e, reader))
+                fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+                    match source.read_value($num, ctx)? {
+                        $kind(value) => Ok(value),
+                        param => Err(wrong_param($num, &param)),
+                    }
+                }
+
+                fn fallback() -> Option<Self> {
+                    Some(Default::default())
+                }
+
+                fn param_number() -> Option<ParamNumber> {
+                    Some($num)
                 }
             }
         )*
     };
 }
 
-impl_read_byte!(
-    (i8, ParamNumber::I8, read_i8),
-    (u8, ParamNumber::U8, read_u8)
-);
-
 impl_read_value!(
-    (i16, ParamNumber::I16, read_i16),
-    (u16, ParamNumber::U16, read_u16),
-    (i32, ParamNumber::I32, read_i32),
-    (u32, ParamNumber::U32, read_u32),
-    (f32, ParamNumber::Float, read_f32)
+    (bool, ParamNumber::Bool, ParamKind::Bool),
+    (i8, ParamNumber::I8, ParamKind::I8),
+    (u8, ParamNumber::U8, ParamKind::U8),
+    (i16, ParamNumber::I16, ParamKind::I16),
+    (u16, ParamNumber::U16, ParamKind::U16),
+    (i32, ParamNumber::I32, ParamKind::I32),
+    (u32, ParamNumber::U32, ParamKind::U32),
+    (f32, ParamNumber::Float, ParamKind::Float),
+    (String, ParamNumber::String, ParamKind::Str)
 );
 
 impl Prc for Hash40 {
-    fn read_param<R: Read + Seek>(reader: &mut R, offsets: FileOffsets) -> Result<Self> {
-        check_type(reader, ParamNumber::Hash)?;
-        let hash_index = reader
-            .read_u32::<LittleEndian>()
-            .map_err(|e| Error::new(e, reader))?;
-        let end_position = reader
-            .seek(SeekFrom::Current(0))
-            .map_err(|e| Error::new(e, reader))?;
-
-        reader
-            .seek(SeekFrom::Start(offsets.hashes + (hash_index as u64 * 8)))
-            .map_err(|e| Error::new(e, reader))?;
-        let hash = reader
-            .read_hash40::<LittleEndian>()
-            .map_err(|e| Error::new(e, reader))?;
-
-        reader
-            .seek(SeekFrom::Start(end_position))
-            .map_err(|e| Error::new(e, reader))?;
-        Ok(hash)
-    }
-}
-
-impl Prc for String {
-    fn read_param<R: Read + Seek>(reader: &mut R, offsets: FileOffsets) -> Result<Self> {
-        check_type(reader, ParamNumber::String)?;
-        let str_offset = reader
-            .read_u32::<LittleEndian>()
-            .map_err(|e| Error::new(e, reader))?;
-        let end_position = reader
-            .seek(SeekFrom::Current(0))
-            .map_err(|e| Error::new(e, reader))?;
-
-        reader
-            .seek(SeekFrom::Start(offsets.ref_table + str_offset as u64))
-            .map_err(|e| Error::new(e, reader))?;
-        let mut string = String::new();
-
-        loop {
-            let byte = reader.read_u8().map_err(|e| Error::new(e, reader))?;
-            if byte == 0 {
-                break;
-            }
-            string.push(byte as char);
+    fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        match source.read_value(ParamNumber::Hash, ctx)? {
+            ParamKind::Hash(value) => Ok(value),
+            param => Err(wrong_param(ParamNumber::Hash, &param)),
         }
+    }
 
-        reader
-            .seek(SeekFrom::Start(end_position))
-            .map_err(|e| Error::new(e, reader))?;
-        Ok(string)
+    fn fallback() -> Option<Self> {
+        Some(Hash40(0))
+    }
+
+    fn param_number() -> Option<ParamNumber> {
+        Some(ParamNumber::Hash)
     }
 }
 
 impl<T: Prc> Prc for Vec<T> {
-    fn read_param<R: Read + Seek>(reader: &mut R, offsets: FileOffsets) -> Result<Self> {
-        let start = reader
-            .seek(SeekFrom::Current(0))
-            .map_err(|e| Error::new(e, reader))?;
-        check_type(reader, ParamNumber::List)?;
-        let len = reader
-            .read_u32::<LittleEndian>()
-            .map_err(|e| Error::new(e, reader))?;
-
-        let mut list = Vec::with_capacity(len as usize);
-
-        for i in 0..len {
-            reader
-                .seek(SeekFrom::Start(start + 5 + (i as u64 * 4)))
-                .map_err(|e| Error::new(e, reader))?;
-            let offset = reader
-                .read_u32::<LittleEndian>()
-                .map_err(|e| Error::new(e, reader))?;
-            reader
-                .seek(SeekFrom::Start(start + offset as u64))
-                .map_err(|e| Error::new(e, reader))?;
-
-            // read the type, and potentially add index to the error path
-            let child = T::read_param(reader, offsets).map_err(|mut e| {
-                e.path.insert(0, ErrorPathPart::Index(i));
-                Error {
-                    path: e.path,
-                    position: e.position,
-                    kind: e.kind,
-                }
-            })?;
-            list.push(child);
-        }
+    fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        let list_data = ListData::from_stream(source)?;
+        (0..list_data.len)
+            .map(|i| list_data.read_child(source, i, ctx))
+            .collect()
+    }
+
+    fn fallback() -> Option<Self> {
+        Some(Vec::new())
+    }
 
-        Ok(list)
+    fn param_number() -> Option<ParamNumber> {
+        Some(ParamNumber::List)
+    }
+
+    fn read_param_diagnostic<S: ParamSource>(
+        source: &mut S,
+        ctx: &ReadContext,
+        errors: &mut Vec<Error>,
+    ) -> Option<Self> {
+        let list_data = match ListData::from_stream(source) {
+            Ok(list_data) => list_data,
+            Err(e) => {
+                errors.push(e);
+                return Self::fallback();
+            }
+        };
+        // read every child before giving up, so all of their errors are found
+        (0..list_data.len)
+            .map(|i| list_data.read_child_diagnostic(source, i, ctx, errors))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect()
     }
 }
 
 impl<T: Prc> Prc for Option<T> {
-    fn read_param<R: Read + Seek>(_: &mut R, _: FileOffsets) -> Result<Self> {
-        unimplemented!("Option's should be read only by using the `read_from_struct` method")
+    /// Outside of a struct the param is always there, such as in a list or
+    /// a map, so it's read as [Some]
+    fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Self> {
+        T::read_param(source, ctx).map(Some)
     }
 
-    fn read_from_struct<R: Read + Seek>(
-        reader: &mut R,
+    fn read_from_struct<S: ParamSource>(
+        source: &mut S,
         hash: Hash40,
-        offsets: FileOffsets,
-        struct_data: StructData,
+        ctx: &ReadContext,
+        struct_data: StructData<S::Node>,
     ) -> Result<Self> {
-        struct_data
-            .search_child(reader, hash, offsets)
-            .and_then(|_| T::read_param(reader, offsets))
-            .map(|param| Some(param))
-            .or_else(|err| match &err.kind {
-                ErrorKind::ParamNotFound(_) => Ok(None),
-                _ => Err(err),
-            })
+        match struct_data.search_child(source, hash, ctx) {
+            Ok(()) => T::read_param(source, ctx).map(Some).map_err(|mut e| {
+                e.path.insert(0, ErrorPathPart::Hash(hash));
+                e
+            }),
+            Err(Error {
+                kind: ErrorKind::ParamNotFound(_),
+                ..
+            }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn fallback() -> Option<Self> {
+        Some(None)
+    }
+
+    fn param_number() -> Option<ParamNumber> {
+        T::param_number()
+    }
+
+    fn read_param_diagnostic<S: ParamSource>(
+        source: &mut S,
+        ctx: &ReadContext,
+        errors: &mut Vec<Error>,
+    ) -> Option<Self> {
+        Some(T::read_param_diagnostic(source, ctx, errors))
     }
 }
 
@@ -418,12 +1044,43 @@ impl TryFrom<u8> for ParamNumber {
     }
 }
 
+/// Placeholders from lenient reads don't have a param number
+impl<'a> TryFrom<&'a ParamKind> for ParamNumber {
+    type Error = &'a Placeholder;
+
+    fn try_from(param: &'a ParamKind) -> std::result::Result<Self, Self::Error> {
+        Ok(match param {
+            ParamKind::Bool(_) => ParamNumber::Bool,
+            ParamKind::I8(_) => ParamNumber::I8,
+            ParamKind::U8(_) => ParamNumber::U8,
+            ParamKind::I16(_) => ParamNumber::I16,
+            ParamKind::U16(_) => ParamNumber::U16,
+            ParamKind::I32(_) => ParamNumber::I32,
+            ParamKind::U32(_) => ParamNumber::U32,
+            ParamKind::Float(_) => ParamNumber::Float,
+            ParamKind::Hash(_) => ParamNumber::Hash,
+            ParamKind::Str(_) => ParamNumber::String,
+            ParamKind::List(_) => ParamNumber::List,
+            ParamKind::Struct(_) => ParamNumber::Struct,
+            ParamKind::Placeholder(placeholder) => return Err(placeholder),
+        })
+    }
+}
+
 impl From<ParamNumber> for u8 {
     fn from(param_num: ParamNumber) -> Self {
         param_num as u8
     }
 }
 
+/// The position of errors for params in memory
+fn no_position() -> std::io::Result<u64> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "params in memory have no position",
+    ))
+}
+
 impl Error {
     fn new<E: Into<ErrorKind>, S: Seek>(kind: E, seek: &mut S) -> Self {
         Error {
@@ -433,17 +1090,172 @@ impl Error {
         }
     }
 
-    fn new_with_pos<E: Into<ErrorKind>>(kind: E, pos: std::io::Result<u64>) -> Self {
+    /// Creates an error at the given position in the reader
+    pub fn new_with_pos<E: Into<ErrorKind>>(kind: E, pos: std::io::Result<u64>) -> Self {
         Error {
             path: vec![],
             position: pos,
             kind: kind.into(),
         }
     }
+
+    /// Params in memory don't have a position, so the position is an error instead
+    pub fn new_in_memory<E: Into<ErrorKind>>(kind: E) -> Self {
+        Error {
+            path: vec![],
+            position: no_position(),
+            kind: kind.into(),
+        }
+    }
 }
 
 impl From<std::io::Error> for ErrorKind {
     fn from(e: std::io::Error) -> Self {
-        ErrorKind::Io(e)
+        match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEof(e),
+            std::io::ErrorKind::InvalidData => ErrorKind::InvalidData(e),
+            _ => ErrorKind::Io(e),
+        }
+    }
+}
+
+impl fmt::Display for ParamNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ParamNumber::Bool => "bool",
+            ParamNumber::I8 => "i8",
+            ParamNumber::U8 => "u8",
+            ParamNumber::I16 => "i16",
+            ParamNumber::U16 => "u16",
+            ParamNumber::I32 => "i32",
+            ParamNumber::U32 => "u32",
+            ParamNumber::Float => "float",
+            ParamNumber::Hash => "hash40",
+            ParamNumber::String => "string",
+            ParamNumber::List => "list",
+            ParamNumber::Struct => "struct",
+        })
+    }
+}
+
+impl fmt::Display for ErrorPathPart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorPathPart::Index(index) => write!(f, "[{}]", index),
+            ErrorPathPart::Hash(hash) => write!(f, ".{}", hash),
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::WrongParamNumber { expected, received } => {
+                match ParamNumber::try_from(*received) {
+                    Ok(received) => write!(f, "expected {}, received {}", expected, received),
+                    Err(received) => {
+                        write!(
+                            f,
+                            "expected {}, received unknown type {}",
+                            expected, received
+                        )
+                    }
+                }
+            }
+            ErrorKind::ParamNotFound(_) => f.write_str("param not found"),
+            ErrorKind::MissingForVersion { version, .. } => {
+                write!(f, "param not found, but required for version {}", version)
+            }
+            ErrorKind::UnknownParam(_) => f.write_str("unknown param"),
+            ErrorKind::Conversion(message) => write!(f, "conversion failed: {}", message),
+            ErrorKind::DuplicateKey { key, first_index } => write!(
+                f,
+                "duplicate key {}, first used at index {}",
+                key, first_index
+            ),
+            ErrorKind::NotInRange { value, range } => {
+                write!(f, "value {} is outside of range {}", value, range)
+            }
+            ErrorKind::InvalidLength { expected, received } => write!(
+                f,
+                "expected length {}, received length {}",
+                expected, received
+            ),
+            ErrorKind::Validation(message) => write!(f, "validation failed: {}", message),
+            ErrorKind::WrongListLength { expected, received } => write!(
+                f,
+                "expected list of length {}, received length {}",
+                expected, received
+            ),
+            ErrorKind::UnknownValue(param) => {
+                f.write_str("value ")?;
+                match param {
+                    ParamKind::Bool(value) => write!(f, "{}", value)?,
+                    ParamKind::I8(value) => write!(f, "{}", value)?,
+                    ParamKind::U8(value) => write!(f, "{}", value)?,
+                    ParamKind::I16(value) => write!(f, "{}", value)?,
+                    ParamKind::U16(value) => write!(f, "{}", value)?,
+                    ParamKind::I32(value) => write!(f, "{}", value)?,
+                    ParamKind::U32(value) => write!(f, "{}", value)?,
+                    ParamKind::Float(value) => write!(f, "{}", value)?,
+                    ParamKind::Hash(value) => write!(f, "{}", value)?,
+                    ParamKind::Str(value) => write!(f, "{:?}", value)?,
+                    _ => match ParamNumber::try_from(param) {
+                        Ok(number) => write!(f, "of type {}", number)?,
+                        Err(_) => f.write_str("placeholder")?,
+                    },
+                }
+                f.write_str(" doesn't match any variant")
+            }
+            ErrorKind::OutOfRange { value, expected } => {
+                write!(f, "value {} doesn't fit in {}", value, expected)
+            }
+            ErrorKind::UnexpectedEof(_) => f.write_str("unexpected end of file"),
+            ErrorKind::InvalidData(e) => write!(f, "invalid data: {}", e),
+            ErrorKind::Io(e) => write!(f, "io error: {}", e),
+            #[cfg(feature = "xml-feat")]
+            ErrorKind::Xml(e) => write!(f, "invalid xml: {}", e),
+        }
+    }
+}
+
+/// Displays the path using the labels of the hashes, such as
+/// `cliff_hang_data[0].fake_name`, followed by the kind and position
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // the missing or unknown hash isn't part of the path, but it reads better there
+        let missing = match self.kind {
+            ErrorKind::ParamNotFound(hash)
+            | ErrorKind::MissingForVersion { hash, .. }
+            | ErrorKind::UnknownParam(hash) => Some(ErrorPathPart::Hash(hash)),
+            _ => None,
+        };
+        let mut path = self.path.iter().chain(missing.iter()).peekable();
+
+        match path.next() {
+            Some(ErrorPathPart::Hash(hash)) => write!(f, "{}", hash)?,
+            Some(index) => write!(f, "{}", index)?,
+            None => f.write_str("<root>")?,
+        }
+        for part in path {
+            write!(f, "{}", part)?;
+        }
+
+        write!(f, ": {}", self.kind)?;
+        if let Ok(position) = self.position {
+            write!(f, " at position 0x{:x}", position)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::UnexpectedEof(e) | ErrorKind::InvalidData(e) | ErrorKind::Io(e) => Some(e),
+            #[cfg(feature = "xml-feat")]
+            ErrorKind::Xml(e) => Some(e),
+            _ => None,
+        }
     }
 }
@@ -0,0 +1,453 @@
+// the inputs which a Prc type can be read from
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::sync::Arc;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use hash40::Hash40;
+
+#[cfg(feature = "xml-feat")]
+use std::io::BufRead;
+
+use super::{
+    check_type, no_position, prepare, wrong_param, Error, ErrorKind, ListData, ParamNumber, Prc,
+    ReadContext, Result, StructData, Version,
+};
+use crate::param::ParamKind;
+
+/// The params which [Prc::read_param] reads from, such as a binary file or
+/// params in memory. Every type reads from any source through these
+/// methods, so values and errors have the same kinds and paths whichever
+/// source is used. Only binary files have positions for their errors.
+///
+/// Implemented for binary readers, where each param is a position in the
+/// file, and for [ParamTree], where each param is a reference to it.
+pub trait ParamSource {
+    /// Identifies a param of the source
+    type Node: Copy;
+
+    /// The param the source is at
+    fn node(&mut self) -> Result<Self::Node>;
+
+    /// Moves the source to the param
+    fn seek_node(&mut self, node: Self::Node) -> Result<()>;
+
+    /// The position of the param in the file, for the errors about it.
+    /// Sources which aren't files have no positions.
+    fn position(&self, node: Self::Node) -> io::Result<u64>;
+
+    /// The type of the param the source is at, which may not be a valid
+    /// [ParamNumber]. The source isn't moved.
+    fn peek_number(&mut self) -> Result<u8>;
+
+    /// Reads the param the source is at, after checking it has the
+    /// expected type
+    fn read_value(&mut self, expected: ParamNumber, ctx: &ReadContext) -> Result<ParamKind>;
+
+    /// Reads the param the source is at, whatever its type
+    fn read_dynamic(&mut self, ctx: &ReadContext) -> Result<ParamKind>;
+
+    /// Reads the struct the source is at. The source isn't moved.
+    fn read_struct(&mut self) -> Result<StructData<Self::Node>>;
+
+    /// Finds the child param of the struct with the hash. If the struct has
+    /// the hash more than once, the first child in the order of
+    /// [read_stream](crate::read_stream) is found.
+    fn find_child(
+        &mut self,
+        struct_data: &StructData<Self::Node>,
+        hash: Hash40,
+        ctx: &ReadContext,
+    ) -> Result<Option<Self::Node>>;
+
+    /// The hash and param of every child of the struct, in the same order as
+    /// [read_stream](crate::read_stream)
+    fn children(
+        &mut self,
+        struct_data: &StructData<Self::Node>,
+        ctx: &ReadContext,
+    ) -> Result<Vec<(Hash40, Self::Node)>>;
+
+    /// Reads the list the source is at
+    fn read_list(&mut self) -> Result<ListData<Self::Node>>;
+
+    /// The child param of the list at `index`, which must be less than its
+    /// length
+    fn list_child(&mut self, list_data: &ListData<Self::Node>, index: u32) -> Result<Self::Node>;
+
+    /// The bytes of the whole file and the position of the param in it, for
+    /// sources which are binary files. Lets [Lazy](super::Lazy) decode the
+    /// param later without the source. Other sources return [None].
+    fn file_data(
+        &mut self,
+        _node: Self::Node,
+        _ctx: &ReadContext,
+    ) -> Result<Option<(Arc<[u8]>, u64)>> {
+        Ok(None)
+    }
+}
+
+/// A binary file, with the tables of the file in the [ReadContext]
+impl<R: Read + Seek> ParamSource for R {
+    type Node = u64;
+
+    fn node(&mut self) -> Result<u64> {
+        self.stream_position().map_err(|e| Error::new(e, self))
+    }
+
+    fn seek_node(&mut self, node: u64) -> Result<()> {
+        self.seek(SeekFrom::Start(node))
+            .map(drop)
+            .map_err(|e| Error::new(e, self))
+    }
+
+    fn position(&self, node: u64) -> io::Result<u64> {
+        Ok(node)
+    }
+
+    fn peek_number(&mut self) -> Result<u8> {
+        let number = self.read_u8().map_err(|e| Error::new(e, self))?;
+        self.seek(SeekFrom::Current(-1))
+            .map_err(|e| Error::new(e, self))?;
+        Ok(number)
+    }
+
+    fn read_value(&mut self, expected: ParamNumber, ctx: &ReadContext) -> Result<ParamKind> {
+        let position = self.stream_position();
+        let received = self.read_u8().map_err(|e| Error::new(e, self))?;
+        if received != expected.into() {
+            return Err(Error::new_with_pos(
+                ErrorKind::WrongParamNumber { expected, received },
+                position,
+            ));
+        }
+        let value = match expected {
+            ParamNumber::Bool => self.read_u8().map(|byte| ParamKind::Bool(byte > 0)),
+            ParamNumber::I8 => self.read_i8().map(ParamKind::I8),
+            ParamNumber::U8 => self.read_u8().map(ParamKind::U8),
+            ParamNumber::I16 => self.read_i16::<LittleEndian>().map(ParamKind::I16),
+            ParamNumber::U16 => self.read_u16::<LittleEndian>().map(ParamKind::U16),
+            ParamNumber::I32 => self.read_i32::<LittleEndian>().map(ParamKind::I32),
+            ParamNumber::U32 => self.read_u32::<LittleEndian>().map(ParamKind::U32),
+            ParamNumber::Float => self.read_f32::<LittleEndian>().map(ParamKind::Float),
+            ParamNumber::Hash => self
+                .read_u32::<LittleEndian>()
+                .and_then(|index| ctx.hash(index))
+                .map(ParamKind::Hash),
+            ParamNumber::String => self
+                .read_u32::<LittleEndian>()
+                .and_then(|offset| ctx.string(self, offset))
+                .map(ParamKind::Str),
+            // params with children are read from the start
+            ParamNumber::List | ParamNumber::Struct => {
+                self.seek(SeekFrom::Current(-1))
+                    .map_err(|e| Error::new(e, self))?;
+                return self.read_dynamic(ctx);
+            }
+        };
+        value.map_err(|e| Error::new(e, self))
+    }
+
+    fn read_dynamic(&mut self, ctx: &ReadContext) -> Result<ParamKind> {
+        crate::disasm::read_param_with_tables(self, ctx.hash_table(), ctx.offsets().ref_table)
+            .map_err(|e| Error::new(e, self))
+    }
+
+    fn read_struct(&mut self) -> Result<StructData> {
+        let node = self.node()?;
+        check_type(self, ParamNumber::Struct)?;
+        let len = self
+            .seek(SeekFrom::Start(node + 1))
+            .and_then(|_| self.read_u32::<LittleEndian>())
+            .map_err(|e| Error::new(e, self))?;
+        self.seek_node(node)?;
+        Ok(StructData { node, len })
+    }
+
+    fn find_child(
+        &mut self,
+        struct_data: &StructData,
+        hash: Hash40,
+        ctx: &ReadContext,
+    ) -> Result<Option<u64>> {
+        let ref_offset = ref_offset(self, struct_data)?;
+        // the ref table is sorted by hash, so find the first entry with it
+        let (mut low, mut high) = (0, struct_data.len);
+        while low < high {
+            let i = low + (high - low) / 2;
+            let (_, read_hash, _) = ref_child(self, ctx, struct_data, ref_offset, i)?;
+            match read_hash.cmp(&hash) {
+                Ordering::Less => low = i + 1,
+                _ => high = i,
+            }
+        }
+        // read_stream orders the children by the index of their hash, so a
+        // duplicate hash is found the same way
+        let mut found: Option<(u32, u64)> = None;
+        for i in low..struct_data.len {
+            let (hash_index, read_hash, node) = ref_child(self, ctx, struct_data, ref_offset, i)?;
+            if read_hash != hash {
+                break;
+            }
+            if found.is_none_or(|(first_index, _)| hash_index < first_index) {
+                found = Some((hash_index, node));
+            }
+        }
+        Ok(found.map(|(_, node)| node))
+    }
+
+    fn children(
+        &mut self,
+        struct_data: &StructData,
+        ctx: &ReadContext,
+    ) -> Result<Vec<(Hash40, u64)>> {
+        let ref_offset = ref_offset(self, struct_data)?;
+        let mut children = (0..struct_data.len)
+            .map(|i| ref_child(self, ctx, struct_data, ref_offset, i))
+            .collect::<Result<Vec<_>>>()?;
+        // stable, so duplicate hashes keep the order of the ref table
+        children.sort_by_key(|(hash_index, _, _)| *hash_index);
+        Ok(children
+            .into_iter()
+            .map(|(_, hash, node)| (hash, node))
+            .collect())
+    }
+
+    fn read_list(&mut self) -> Result<ListData> {
+        let node = self.node()?;
+        check_type(self, ParamNumber::List)?;
+        let len = self
+            .seek(SeekFrom::Start(node + 1))
+            .and_then(|_| self.read_u32::<LittleEndian>())
+            .map_err(|e| Error::new(e, self))?;
+        Ok(ListData { node, len })
+    }
+
+    fn list_child(&mut self, list_data: &ListData, index: u32) -> Result<u64> {
+        self.seek(SeekFrom::Start(list_data.node + 5 + (index as u64 * 4)))
+            .and_then(|_| self.read_u32::<LittleEndian>())
+            .map(|offset| list_data.node + offset as u64)
+            .map_err(|e| Error::new(e, self))
+    }
+
+    fn file_data(&mut self, node: u64, ctx: &ReadContext) -> Result<Option<(Arc<[u8]>, u64)>> {
+        let data = ctx.file_data(self).map_err(|e| Error::new(e, self))?;
+        Ok(Some((data, node)))
+    }
+}
+
+/// The offset of the struct's entries in the ref table
+fn ref_offset<R: Read + Seek>(reader: &mut R, struct_data: &StructData) -> Result<u32> {
+    reader
+        .seek(SeekFrom::Start(struct_data.node + 5))
+        .and_then(|_| reader.read_u32::<LittleEndian>())
+        .map_err(|e| Error::new(e, reader))
+}
+
+/// Reads the ref table entry of the struct's child at `i`. Returns the index
+/// of its hash, the hash, and the position of the child.
+fn ref_child<R: Read + Seek>(
+    reader: &mut R,
+    ctx: &ReadContext,
+    struct_data: &StructData,
+    ref_offset: u32,
+    i: u32,
+) -> Result<(u32, Hash40, u64)> {
+    let (hash_index, param_offset) = ctx
+        .ref_entry(reader, ref_offset as u64 + (i as u64 * 8))
+        .map_err(|e| Error::new(e, reader))?;
+    let hash = ctx.hash(hash_index).map_err(|e| Error::new(e, reader))?;
+    Ok((hash_index, hash, struct_data.node + param_offset as u64))
+}
+
+/// Params in memory, such as ones from [read_stream](crate::read_stream) or
+/// XML, read with the same [Prc] implementations as binary files. Errors
+/// have no position, except for [Placeholder](crate::param::Placeholder)s,
+/// which have the position they had in their file.
+#[derive(Debug, Clone, Copy)]
+pub struct ParamTree<'a> {
+    node: &'a ParamKind,
+}
+
+impl<'a> ParamTree<'a> {
+    /// A source at the root param
+    pub fn new(root: &'a ParamKind) -> Self {
+        ParamTree { node: root }
+    }
+}
+
+impl<'a> ParamSource for ParamTree<'a> {
+    type Node = &'a ParamKind;
+
+    fn node(&mut self) -> Result<&'a ParamKind> {
+        Ok(self.node)
+    }
+
+    fn seek_node(&mut self, node: &'a ParamKind) -> Result<()> {
+        self.node = node;
+        Ok(())
+    }
+
+    fn position(&self, _node: &'a ParamKind) -> io::Result<u64> {
+        no_position()
+    }
+
+    fn peek_number(&mut self) -> Result<u8> {
+        match ParamNumber::try_from(self.node) {
+            Ok(number) => Ok(number.into()),
+            // report why the param couldn't be decoded, where it was in the file
+            Err(placeholder) => Err(Error::new_with_pos(
+                ErrorKind::InvalidData(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    placeholder.error.clone(),
+                )),
+                Ok(placeholder.position),
+            )),
+        }
+    }
+
+    fn read_value(&mut self, expected: ParamNumber, _ctx: &ReadContext) -> Result<ParamKind> {
+        check_type(self, expected)?;
+        Ok(self.node.clone())
+    }
+
+    fn read_dynamic(&mut self, _ctx: &ReadContext) -> Result<ParamKind> {
+        Ok(self.node.clone())
+    }
+
+    fn read_struct(&mut self) -> Result<StructData<&'a ParamKind>> {
+        match self.node {
+            ParamKind::Struct(param_struct) => Ok(StructData {
+                node: self.node,
+                len: param_struct.0.len() as u32,
+            }),
+            param => Err(wrong_param(ParamNumber::Struct, param)),
+        }
+    }
+
+    fn find_child(
+        &mut self,
+        struct_data: &StructData<&'a ParamKind>,
+        hash: Hash40,
+        _ctx: &ReadContext,
+    ) -> Result<Option<&'a ParamKind>> {
+        Ok(struct_children(struct_data.node)
+            .iter()
+            .find(|(child_hash, _)| *child_hash == hash)
+            .map(|(_, child)| child))
+    }
+
+    fn children(
+        &mut self,
+        struct_data: &StructData<&'a ParamKind>,
+        _ctx: &ReadContext,
+    ) -> Result<Vec<(Hash40, &'a ParamKind)>> {
+        Ok(struct_children(struct_data.node)
+            .iter()
+            .map(|(hash, child)| (*hash, child))
+            .collect())
+    }
+
+    fn read_list(&mut self) -> Result<ListData<&'a ParamKind>> {
+        match self.node {
+            ParamKind::List(list) => Ok(ListData {
+                node: self.node,
+                len: list.0.len() as u32,
+            }),
+            param => Err(wrong_param(ParamNumber::List, param)),
+        }
+    }
+
+    fn list_child(
+        &mut self,
+        list_data: &ListData<&'a ParamKind>,
+        index: u32,
+    ) -> Result<&'a ParamKind> {
+        let children = match list_data.node {
+            ParamKind::List(list) => &list.0[..],
+            _ => &[],
+        };
+        children.get(index as usize).ok_or_else(|| {
+            Error::new_with_pos(
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("index {} is outside of the list", index),
+                ),
+                no_position(),
+            )
+        })
+    }
+}
+
+/// The children of the param, if it's a struct
+fn struct_children(param: &ParamKind) -> &[(Hash40, ParamKind)] {
+    match param {
+        ParamKind::Struct(param_struct) => &param_struct.0,
+        _ => &[],
+    }
+}
+
+/// A convenience entry point for reading any [Prc] type from a binary file,
+/// a param in memory or an XML document. Each is read through a
+/// [ParamSource], so values and errors have the same kinds and paths
+/// whichever input is used. Only binary files and XML syntax errors have
+/// positions.
+pub trait ParamInput: Sized {
+    /// Reads the root param of the input as T, reading fields with the
+    /// `since`, `until` and `if` attributes for the game version if there's
+    /// one
+    fn read_root<T: Prc>(self, version: Option<Version>) -> Result<T>;
+
+    /// Reads the root param of the input as T
+    fn read_prc<T: Prc>(self) -> Result<T> {
+        self.read_root(None)
+    }
+}
+
+/// A binary file, which should be positioned at the header
+impl<R: Read + Seek> ParamInput for &mut R {
+    fn read_root<T: Prc>(self, version: Option<Version>) -> Result<T> {
+        let mut ctx = prepare(self)?;
+        if let Some(version) = version {
+            ctx = ctx.with_version(version);
+        }
+        T::read_param(self, &ctx)
+    }
+}
+
+/// The bytes of a binary file
+impl ParamInput for &[u8] {
+    fn read_root<T: Prc>(self, version: Option<Version>) -> Result<T> {
+        (&mut Cursor::new(self)).read_root(version)
+    }
+}
+
+/// A param in memory, such as one from [read_stream](crate::read_stream),
+/// read through a [ParamTree]
+impl ParamInput for &ParamKind {
+    fn read_root<T: Prc>(self, version: Option<Version>) -> Result<T> {
+        let mut ctx = ReadContext::default();
+        if let Some(version) = version {
+            ctx = ctx.with_version(version);
+        }
+        T::read_param(&mut ParamTree::new(self), &ctx)
+    }
+}
+
+/// An XML document in the format of [write_xml](crate::xml::write_xml). The
+/// document is parsed into params in memory first, so only syntax errors have
+/// a position, which is the byte offset of the error in the document.
+#[cfg(feature = "xml-feat")]
+#[derive(Debug)]
+pub struct Xml<R>(pub R);
+
+#[cfg(feature = "xml-feat")]
+impl<R: BufRead> ParamInput for Xml<R> {
+    fn read_root<T: Prc>(mut self, version: Option<Version>) -> Result<T> {
+        let param_struct = crate::xml::read_xml(&mut self.0)
+            .map_err(|e| Error::new_with_pos(ErrorKind::Xml(e.error), Ok(e.start as u64)))?;
+        (&ParamKind::Struct(param_struct)).read_root(version)
+    }
+}
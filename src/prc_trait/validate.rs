@@ -0,0 +1,64 @@
+// checks for the validation attributes of derived structs
+
+use std::fmt;
+use std::ops::RangeBounds;
+
+use hash40::Hash40;
+
+use super::{Error, ErrorKind, ErrorPathPart, ParamSource, ReadContext, StructData};
+
+/// Checks that the value is within `range`. Used by the derive macro for
+/// fields with the `#[prc(range = ...)]` attribute.
+pub fn check_range<T, B>(value: &T, range: B) -> Result<(), ErrorKind>
+where
+    T: PartialOrd + fmt::Debug,
+    B: RangeBounds<T> + fmt::Debug,
+{
+    if range.contains(value) {
+        Ok(())
+    } else {
+        Err(ErrorKind::NotInRange {
+            value: format!("{:?}", value),
+            range: format!("{:?}", range),
+        })
+    }
+}
+
+/// Checks that the length of the value is `expected`. Used by the derive
+/// macro for fields with the `#[prc(len = ...)]` attribute.
+pub fn check_len(len: usize, expected: usize) -> Result<(), ErrorKind> {
+    if len == expected {
+        Ok(())
+    } else {
+        Err(ErrorKind::InvalidLength {
+            expected,
+            received: len,
+        })
+    }
+}
+
+/// Converts the result of a validation function, keeping the message of its
+/// error. Used by the derive macro for fields with the
+/// `#[prc(validate = "...")]` attribute.
+pub fn check_valid<E: fmt::Display>(result: Result<(), E>) -> Result<(), ErrorKind> {
+    result.map_err(|e| ErrorKind::Validation(e.to_string()))
+}
+
+/// Creates the error for a child of the struct which failed validation, at
+/// the position of the child param
+pub fn invalid_child<S: ParamSource>(
+    source: &mut S,
+    hash: Hash40,
+    ctx: &ReadContext,
+    struct_data: StructData<S::Node>,
+    kind: ErrorKind,
+) -> Error {
+    // a missing child was replaced with its default, so use the struct instead
+    let node = match source.find_child(&struct_data, hash, ctx) {
+        Ok(Some(node)) => node,
+        _ => struct_data.node,
+    };
+    let mut error = Error::new_with_pos(kind, source.position(node));
+    error.path.push(ErrorPathPart::Hash(hash));
+    error
+}
@@ -0,0 +1,155 @@
+// game versions, for fields which only exist in some versions of a file
+
+use std::fmt;
+use std::str::FromStr;
+
+use hash40::Hash40;
+
+use super::{Error, ErrorKind, Result};
+
+/// A version of the game, such as `13.0.1`. Set on the [ReadContext](super::ReadContext)
+/// to read fields with the `since`, `until` and `if` attributes for that version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Version {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses versions like `13.0.1`, or returns [None] if the string isn't
+    /// one to three numbers separated by dots. Missing minor and patch
+    /// numbers are 0. This is const, so the derive macro can check the
+    /// versions of attributes when compiling.
+    pub const fn parse(s: &str) -> Option<Self> {
+        let bytes = s.as_bytes();
+        let mut parts = [0u32; 3];
+        let mut part = 0;
+        let mut digits = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            let byte = bytes[i];
+            if byte == b'.' {
+                if digits == 0 || part == 2 {
+                    return None;
+                }
+                part += 1;
+                digits = 0;
+            } else if byte.is_ascii_digit() {
+                parts[part] = match parts[part].checked_mul(10) {
+                    Some(value) => match value.checked_add((byte - b'0') as u32) {
+                        Some(value) => value,
+                        None => return None,
+                    },
+                    None => return None,
+                };
+                digits += 1;
+            } else {
+                return None;
+            }
+            i += 1;
+        }
+        if digits == 0 {
+            return None;
+        }
+        Some(Version::new(parts[0], parts[1], parts[2]))
+    }
+
+    /// Parses the version of a `since` or `until` attribute, failing the
+    /// build if it's invalid. Used by the derive macro.
+    #[doc(hidden)]
+    pub const fn from_attribute(s: &str) -> Self {
+        match Version::parse(s) {
+            Some(version) => version,
+            None => panic!(
+                "invalid version, expected one to three numbers separated by dots, like \"13.0.1\""
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The error from parsing a [Version] which isn't one to three numbers
+/// separated by dots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseVersionError;
+
+impl fmt::Display for ParseVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("expected a version like 13.0.1")
+    }
+}
+
+impl std::error::Error for ParseVersionError {}
+
+/// Parses versions like `13.0.1`, the same way as [Version::parse]
+impl FromStr for Version {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Version::parse(s).ok_or(ParseVersionError)
+    }
+}
+
+/// Whether the error is for the child param with the hash missing, and not
+/// an error from inside it
+fn is_missing(e: &Error, hash: Hash40) -> bool {
+    e.path.is_empty() && matches!(e.kind, ErrorKind::ParamNotFound(missing) if missing == hash)
+}
+
+/// Handles the child param missing, for a field whose version attributes
+/// matched `version`. Without a version, the default is used, since the param
+/// might not be in the file's version. Otherwise the param is required, and
+/// reported as [ErrorKind::MissingForVersion]. Used by the derive macro for
+/// fields with the `since`, `until` and `if` attributes.
+pub fn require_for_version<T>(
+    result: Result<T>,
+    hash: Hash40,
+    version: Option<Version>,
+    default: impl FnOnce() -> T,
+) -> Result<T> {
+    match (result, version) {
+        (Err(e), None) if is_missing(&e, hash) => Ok(default()),
+        (Err(mut e), Some(version)) if is_missing(&e, hash) => {
+            e.kind = ErrorKind::MissingForVersion { hash, version };
+            Err(e)
+        }
+        (result, _) => result,
+    }
+}
+
+/// The diagnostic counterpart to [require_for_version]
+pub fn require_for_version_diagnostic<T>(
+    hash: Hash40,
+    version: Option<Version>,
+    errors: &mut Vec<Error>,
+    read: impl FnOnce(&mut Vec<Error>) -> Option<T>,
+    default: impl FnOnce() -> T,
+) -> Option<T> {
+    let start = errors.len();
+    let value = read(errors);
+    match (&mut errors[start..], version) {
+        ([e], None) if is_missing(e, hash) => {
+            errors.pop();
+            Some(default())
+        }
+        ([e], Some(version)) if is_missing(e, hash) => {
+            e.kind = ErrorKind::MissingForVersion { hash, version };
+            value
+        }
+        _ => value,
+    }
+}
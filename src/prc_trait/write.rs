@@ -0,0 +1,179 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Seek, Write};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use hash40::Hash40;
+use indexmap::IndexMap;
+
+use crate::param::{ParamKind, ParamList, ParamStruct};
+
+/// A trait allowing a type to be converted into the param container format.
+/// The write-side counterpart to [Prc](super::Prc).
+pub trait PrcWrite {
+    /// Converts self into a param. Returns an error if there's no param for
+    /// the value, such as [None] outside of a struct field.
+    fn to_param(&self) -> Result<ParamKind, IoError>;
+
+    /// Controls how structs should add this value as a child param.
+    /// Like [Prc::read_from_struct](super::Prc::read_from_struct), this
+    /// usually doesn't need to be implemented. For example, [Option] types
+    /// leave out the hash entirely when they are [None].
+    fn write_to_struct(&self, hash: Hash40, param_struct: &mut ParamStruct) -> Result<(), IoError> {
+        param_struct.0.push((hash, self.to_param()?));
+        Ok(())
+    }
+
+    /// A blanket implementation which writes Self as an entire file.
+    /// Returns an error if Self isn't written as a struct, because the root
+    /// param of a file must be one.
+    fn write_file<W: Write + Seek>(&self, writer: &mut W) -> Result<(), IoError> {
+        match self.to_param()? {
+            ParamKind::Struct(param_struct) => crate::write_stream(writer, &param_struct),
+            _ => Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "the root param of a file must be a struct",
+            )),
+        }
+    }
+}
+
+macro_rules! impl_write_value {
+    ($($param_type:ty),*) => {
+        $(
+            impl PrcWrite for $param_type {
+                fn to_param(&self) -> Result<ParamKind, IoError> {
+                    Ok(ParamKind::from(*self))
+                }
+            }
+        )*
+    };
+}
+
+impl_write_value!(bool, i8, u8, i16, u16, i32, u32, f32, Hash40);
+
+impl PrcWrite for String {
+    fn to_param(&self) -> Result<ParamKind, IoError> {
+        Ok(ParamKind::Str(self.clone()))
+    }
+}
+
+impl<T: PrcWrite> PrcWrite for Vec<T> {
+    fn to_param(&self) -> Result<ParamKind, IoError> {
+        self.iter()
+            .map(T::to_param)
+            .collect::<Result<_, _>>()
+            .map(|list| ParamKind::List(ParamList(list)))
+    }
+}
+
+impl<T: PrcWrite> PrcWrite for Option<T> {
+    fn to_param(&self) -> Result<ParamKind, IoError> {
+        match self {
+            Some(value) => value.to_param(),
+            None => Err(IoError::new(
+                IoErrorKind::InvalidInput,
+                "None can only be written as a struct field, where the param is left out",
+            )),
+        }
+    }
+
+    fn write_to_struct(&self, hash: Hash40, param_struct: &mut ParamStruct) -> Result<(), IoError> {
+        match self {
+            Some(value) => value.write_to_struct(hash, param_struct),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: PrcWrite, const N: usize> PrcWrite for [T; N] {
+    fn to_param(&self) -> Result<ParamKind, IoError> {
+        self.iter()
+            .map(T::to_param)
+            .collect::<Result<_, _>>()
+            .map(|list| ParamKind::List(ParamList(list)))
+    }
+}
+
+macro_rules! impl_write_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: PrcWrite),+> PrcWrite for ($($name,)+) {
+            fn to_param(&self) -> Result<ParamKind, IoError> {
+                Ok(ParamKind::List(ParamList(vec![$(self.$index.to_param()?),+])))
+            }
+        }
+    };
+}
+
+impl_write_tuple!(A 0);
+impl_write_tuple!(A 0, B 1);
+impl_write_tuple!(A 0, B 1, C 2);
+impl_write_tuple!(A 0, B 1, C 2, D 3);
+impl_write_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_write_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_write_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_write_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+macro_rules! impl_write_pointer {
+    ($($pointer:ident),*) => {
+        $(
+            impl<T: PrcWrite + ?Sized> PrcWrite for $pointer<T> {
+                fn to_param(&self) -> Result<ParamKind, IoError> {
+                    T::to_param(self)
+                }
+
+                fn write_to_struct(&self, hash: Hash40, param_struct: &mut ParamStruct) -> Result<(), IoError> {
+                    T::write_to_struct(self, hash, param_struct)
+                }
+            }
+        )*
+    };
+}
+
+impl_write_pointer!(Box, Rc, Arc);
+
+macro_rules! impl_write_map {
+    ($($map:ty),*) => {
+        $(
+            impl<T: PrcWrite, S> PrcWrite for $map {
+                fn to_param(&self) -> Result<ParamKind, IoError> {
+                    let mut param_struct = ParamStruct(Vec::with_capacity(self.len()));
+                    for (hash, value) in self {
+                        value.write_to_struct(*hash, &mut param_struct)?;
+                    }
+                    Ok(ParamKind::Struct(param_struct))
+                }
+            }
+        )*
+    };
+}
+
+impl_write_map!(HashMap<Hash40, T, S>, IndexMap<Hash40, T, S>);
+
+impl<T: PrcWrite> PrcWrite for BTreeMap<Hash40, T> {
+    fn to_param(&self) -> Result<ParamKind, IoError> {
+        let mut param_struct = ParamStruct(Vec::with_capacity(self.len()));
+        for (hash, value) in self {
+            value.write_to_struct(*hash, &mut param_struct)?;
+        }
+        Ok(ParamKind::Struct(param_struct))
+    }
+}
+
+impl PrcWrite for ParamKind {
+    fn to_param(&self) -> Result<ParamKind, IoError> {
+        Ok(self.clone())
+    }
+}
+
+impl PrcWrite for ParamStruct {
+    fn to_param(&self) -> Result<ParamKind, IoError> {
+        Ok(ParamKind::Struct(self.clone()))
+    }
+}
+
+impl PrcWrite for ParamList {
+    fn to_param(&self) -> Result<ParamKind, IoError> {
+        Ok(ParamKind::List(self.clone()))
+    }
+}
@@ -0,0 +1,177 @@
+use crate::asm::RefEntry;
+use crate::param::*;
+use hash40::Hash40;
+use indexmap::IndexSet;
+use std::collections::HashSet;
+
+/// The header size, plus the empty hash which always starts the hash table
+const HEADER_SIZE: u32 = 0x10 + 8;
+
+/// The bytes a param file would take up when assembled, split between the
+/// file header and the root struct.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SizeBreakdown {
+    /// Bytes for the file header and the empty hash at the start of the hash table
+    pub header_bytes: u32,
+    pub root: SubtreeSize,
+}
+
+/// The bytes a param and all of its children add to each section of the file.
+/// Hashes and ref entries which appear more than once are only written once,
+/// so they count towards the first subtree to use them when assembling.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SubtreeSize {
+    /// Bytes added to the hash table. For structs, this includes the hashes of the keys
+    pub hash_bytes: u32,
+    /// Bytes added to the ref table, from strings and struct tables
+    pub ref_bytes: u32,
+    /// Bytes in the param section
+    pub param_bytes: u32,
+    /// The sizes of the child params, in the same order as the param's children.
+    /// Empty for value-type params
+    pub children: Vec<SubtreeSize>,
+}
+
+impl SizeBreakdown {
+    /// The size of the whole file
+    pub fn total(&self) -> u32 {
+        self.header_bytes + self.root.total()
+    }
+}
+
+impl SubtreeSize {
+    /// The size of the subtree across all sections
+    pub fn total(&self) -> u32 {
+        self.hash_bytes + self.ref_bytes + self.param_bytes
+    }
+}
+
+pub fn size_breakdown(param: &ParamStruct) -> SizeBreakdown {
+    let mut hashes = IndexSet::new();
+    hashes.insert(Hash40(0));
+
+    // the same two passes as assembling: hashes first in struct order,
+    // then ref entries in the order the params are written
+    let mut root = struct_hash_sizes(&mut hashes, param);
+    let mut ref_entries = HashSet::new();
+    struct_ref_sizes(&hashes, &mut ref_entries, param, &mut root);
+
+    SizeBreakdown {
+        header_bytes: HEADER_SIZE,
+        root,
+    }
+}
+
+fn hash_sizes(hashes: &mut IndexSet<Hash40>, param: &ParamKind) -> SubtreeSize {
+    match param {
+        ParamKind::Bool(_) | ParamKind::I8(_) | ParamKind::U8(_) => SubtreeSize {
+            param_bytes: 2,
+            ..Default::default()
+        },
+        ParamKind::I16(_) | ParamKind::U16(_) => SubtreeSize {
+            param_bytes: 3,
+            ..Default::default()
+        },
+        ParamKind::I32(_) | ParamKind::U32(_) | ParamKind::Float(_) | ParamKind::Str(_) => {
+            SubtreeSize {
+                param_bytes: 5,
+                ..Default::default()
+            }
+        }
+        ParamKind::Hash(hash) => SubtreeSize {
+            hash_bytes: if hashes.insert(*hash) { 8 } else { 0 },
+            param_bytes: 5,
+            ..Default::default()
+        },
+        ParamKind::List(list) => {
+            let mut size = SubtreeSize {
+                param_bytes: 5 + 4 * list.0.len() as u32,
+                ..Default::default()
+            };
+            for child in &list.0 {
+                let child_size = hash_sizes(hashes, child);
+                size.hash_bytes += child_size.hash_bytes;
+                size.param_bytes += child_size.param_bytes;
+                size.children.push(child_size);
+            }
+            size
+        }
+        ParamKind::Struct(param_struct) => struct_hash_sizes(hashes, param_struct),
+        // can't be written, so it doesn't take up any space
+        ParamKind::Placeholder(_) => SubtreeSize::default(),
+    }
+}
+
+fn struct_hash_sizes(hashes: &mut IndexSet<Hash40>, param_struct: &ParamStruct) -> SubtreeSize {
+    let mut size = SubtreeSize {
+        param_bytes: 9,
+        ..Default::default()
+    };
+    for (hash, child) in &param_struct.0 {
+        if hashes.insert(*hash) {
+            size.hash_bytes += 8;
+        }
+        let child_size = hash_sizes(hashes, child);
+        size.hash_bytes += child_size.hash_bytes;
+        size.param_bytes += child_size.param_bytes;
+        size.children.push(child_size);
+    }
+    size
+}
+
+fn ref_sizes(
+    hashes: &IndexSet<Hash40>,
+    ref_entries: &mut HashSet<RefEntry>,
+    param: &ParamKind,
+    size: &mut SubtreeSize,
+) {
+    match param {
+        ParamKind::Str(val) if ref_entries.insert(RefEntry::RString(val.clone())) => {
+            size.ref_bytes += 1 + val.len() as u32;
+        }
+        ParamKind::List(list) => {
+            for (child, child_size) in list.0.iter().zip(size.children.iter_mut()) {
+                ref_sizes(hashes, ref_entries, child, child_size);
+                size.ref_bytes += child_size.ref_bytes;
+            }
+        }
+        ParamKind::Struct(param_struct) => {
+            struct_ref_sizes(hashes, ref_entries, param_struct, size)
+        }
+        _ => {}
+    }
+}
+
+fn struct_ref_sizes(
+    hashes: &IndexSet<Hash40>,
+    ref_entries: &mut HashSet<RefEntry>,
+    param_struct: &ParamStruct,
+    size: &mut SubtreeSize,
+) {
+    // children are written sorted by hash, same as when assembling
+    let mut sorted = (0..param_struct.0.len()).collect::<Vec<_>>();
+    sorted.sort_by_key(|&i| param_struct.0[i].0);
+
+    let mut offset = 9;
+    let table = sorted
+        .iter()
+        .map(|&i| {
+            let entry = (
+                hashes.get_full(&param_struct.0[i].0).unwrap().0 as u32,
+                offset,
+            );
+            offset += size.children[i].param_bytes;
+            entry
+        })
+        .collect::<Vec<_>>();
+
+    if ref_entries.insert(RefEntry::RTable(table)) {
+        size.ref_bytes += 8 * param_struct.0.len() as u32;
+    }
+
+    for i in sorted {
+        let child_size = &mut size.children[i];
+        ref_sizes(hashes, ref_entries, &param_struct.0[i].1, child_size);
+        size.ref_bytes += child_size.ref_bytes;
+    }
+}
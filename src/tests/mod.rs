@@ -1,13 +1,21 @@
-use crate::prc_trait::{ErrorKind, ErrorPathPart, ParamNumber};
-use crate::{write_stream, ParamKind, ParamStruct, Prc};
+use crate::prc_trait::{
+    prepare, BorrowedList, ErrorKind, ErrorPathPart, ParamInput, ParamNumber, ParamSource,
+    ReadContext, ReadSeek, StructData, Version,
+};
+use crate::{
+    encoded_size, read_stream, read_stream_lenient, size_breakdown, write_stream, ParamKind,
+    ParamList, ParamStruct, Placeholder, Prc, PrcBorrow, PrcWrite,
+};
 
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
 use std::io::Cursor;
 
 use hash40::{hash40, Hash40};
 
 static FIGHTER_PIKACHU_VL: &[u8] = include_bytes!("vl.prc");
 
-#[derive(Debug, Default, PartialEq, Prc)]
+#[derive(Debug, Default, PartialEq, Prc, PrcWrite)]
 #[prc(path = crate)]
 struct FighterPikachuVl {
     map_coll_data: Vec<MapColl>,
@@ -17,7 +25,7 @@ struct FighterPikachuVl {
     ledge_grab_data: Vec<LedgeGrabBox>,
 }
 
-#[derive(Debug, PartialEq, Prc)]
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
 #[prc(path = crate)]
 struct MapColl {
     // you can also target the hash of a param without knowing the name
@@ -28,7 +36,7 @@ struct MapColl {
     offset_z: f32,
 }
 
-#[derive(Debug, PartialEq, Prc)]
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
 #[prc(path = crate)]
 struct LedgeGrabBox {
     p1_x: f32,
@@ -113,7 +121,7 @@ fn test_derive_wrong_param_type_error() {
         _ => panic!("Wrong error encountered"),
     }
     assert_eq!(vl.position.unwrap(), 0xd0b);
-    let expected = vec![
+    let expected = [
         ErrorPathPart::Hash(hash40("hit_target")),
         ErrorPathPart::Index(0),
     ];
@@ -154,7 +162,7 @@ fn test_derive_param_not_found_error() {
         _ => panic!("Wrong error encountered"),
     }
     assert_eq!(vl.position.unwrap(), 0x1071);
-    let expected = vec![
+    let expected = [
         ErrorPathPart::Hash(hash40("cliff_hang_data")),
         ErrorPathPart::Index(0),
     ];
@@ -167,7 +175,7 @@ fn test_derive_param_not_found_error() {
         .all(|(i, path)| path == &expected[i]));
 }
 
-#[derive(Debug, Prc, PartialEq, Eq)]
+#[derive(Debug, Prc, PrcWrite, PartialEq, Eq)]
 #[prc(path = crate)]
 struct OptionalTestStruct {
     required_field: u8,
@@ -180,9 +188,7 @@ fn test_optional_param() {
         (hash40("required_field"), ParamKind::U8(0)),
         (hash40("optional_field"), ParamKind::Bool(true)),
     ]);
-    let param_missing = ParamStruct(vec![
-        (hash40("required_field"), ParamKind::U8(1))
-    ]);
+    let param_missing = ParamStruct(vec![(hash40("required_field"), ParamKind::U8(1))]);
 
     let mut file_present = Cursor::new(vec![]);
     write_stream(&mut file_present, &param_present).unwrap();
@@ -201,3 +207,2363 @@ fn test_optional_param() {
     assert_eq!(data_missing.required_field, 1);
     assert_eq!(data_missing.optional_field, None);
 }
+
+fn large_list_param() -> ParamStruct {
+    // enough entries to take the parallel path when the `rayon` feature is on
+    let entries = (0..500)
+        .map(|i| {
+            ParamKind::Struct(ParamStruct(vec![
+                (
+                    hash40("id"),
+                    ParamKind::Hash(hash40(&format!("entry_{}", i % 50))),
+                ),
+                (hash40("name"), ParamKind::Str(format!("name_{}", i % 20))),
+                (hash40("value"), ParamKind::I32(i)),
+                (
+                    hash40("points"),
+                    ParamKind::List(ParamList(vec![ParamKind::Float(i as f32); 100])),
+                ),
+            ]))
+        })
+        .collect();
+    ParamStruct(vec![(
+        hash40("entries"),
+        ParamKind::List(ParamList(entries)),
+    )])
+}
+
+#[test]
+fn test_large_list_round_trip() {
+    let param = large_list_param();
+
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    file.set_position(0);
+    let read = read_stream(&mut file).unwrap();
+    assert_eq!(read, param);
+
+    let mut rewritten = Cursor::new(vec![]);
+    write_stream(&mut rewritten, &read).unwrap();
+    assert_eq!(encoded_size(&read), rewritten.get_ref().len() as u64);
+    assert_eq!(rewritten.into_inner(), file.into_inner());
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_parallel_matches_sequential() {
+    use crate::{asm::assemble_inner, disasm::disassemble_slice};
+
+    let param = large_list_param();
+    let write = |threshold| {
+        let mut file = Cursor::new(vec![]);
+        assemble_inner(&mut file, &param, threshold).unwrap();
+        file.into_inner()
+    };
+    // every list is encoded in parallel, or none of them
+    let parallel = write(1);
+    let sequential = write(usize::MAX);
+    assert_eq!(parallel, sequential);
+
+    let read = |threshold, lenient| {
+        crate::disasm::disassemble_inner(
+            &mut Cursor::new(&parallel[..]),
+            Some(crate::disasm::Parallel {
+                data: &parallel,
+                threshold,
+            }),
+            lenient,
+        )
+        .unwrap()
+        .param
+    };
+    for lenient in [false, true] {
+        let parallel = read(1, lenient);
+        assert_eq!(parallel, read(usize::MAX, lenient));
+        assert_eq!(parallel, param);
+    }
+    assert_eq!(disassemble_slice(&sequential, false).unwrap().param, param);
+    assert_eq!(read_stream(&mut Cursor::new(&sequential)).unwrap(), param);
+}
+
+#[cfg(feature = "async")]
+#[tokio::test]
+async fn test_async_read_write() {
+    let mut reader = FIGHTER_PIKACHU_VL;
+    let param = crate::read_async(&mut reader).await.unwrap();
+    assert_eq!(
+        param,
+        crate::read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap()
+    );
+
+    let mut written = vec![];
+    crate::write_async(&mut written, &param).await.unwrap();
+    let mut expected = Cursor::new(vec![]);
+    write_stream(&mut expected, &param).unwrap();
+    assert_eq!(written, expected.into_inner());
+
+    let mut reader = FIGHTER_PIKACHU_VL;
+    let typed: FighterPikachuVl = crate::prc_trait::read_file_async(&mut reader)
+        .await
+        .unwrap();
+    assert_eq!(
+        typed,
+        FighterPikachuVl::read_file(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap()
+    );
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_open_mmap() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("vl.prc");
+    std::fs::write(&path, FIGHTER_PIKACHU_VL).unwrap();
+
+    let mapped = crate::open_mmap(&path).unwrap();
+    assert_eq!(&mapped[..], FIGHTER_PIKACHU_VL);
+    assert_eq!(mapped.decode().unwrap(), crate::open(&path).unwrap());
+
+    let typed = FighterPikachuVl::read_file(&mut mapped.reader()).unwrap();
+    assert_eq!(
+        typed,
+        FighterPikachuVl::read_file(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap()
+    );
+
+    let path = dir.path().join("entries.prc");
+    std::fs::write(&path, borrowed_entries_file()).unwrap();
+    let mapped = crate::open_mmap(&path).unwrap();
+    let borrowed = mapped.borrow::<BorrowedEntries>().unwrap();
+    let entry = borrowed.entries.get(3).unwrap().unwrap();
+    assert_eq!(entry.name, "name_3");
+    // the string points into the map instead of being copied
+    assert!(mapped.as_ptr_range().contains(&entry.name.as_ptr()));
+}
+
+#[test]
+fn test_encoded_size() {
+    let param = read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+    assert_eq!(encoded_size(&param), FIGHTER_PIKACHU_VL.len() as u64);
+
+    let breakdown = size_breakdown(&param);
+    let hash_size = u32::from_le_bytes(FIGHTER_PIKACHU_VL[8..12].try_into().unwrap());
+    let ref_size = u32::from_le_bytes(FIGHTER_PIKACHU_VL[12..16].try_into().unwrap());
+    assert_eq!(
+        breakdown.header_bytes + breakdown.root.hash_bytes,
+        0x10 + hash_size
+    );
+    assert_eq!(breakdown.root.ref_bytes, ref_size);
+    assert_eq!(breakdown.root.children.len(), param.0.len());
+
+    // the same string twice only takes up space once, counting towards
+    // whichever key is written first (keys are written in hash order)
+    let param = ParamStruct(vec![
+        (hash40("a"), ParamKind::Str("duplicate".into())),
+        (hash40("b"), ParamKind::Str("duplicate".into())),
+    ]);
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    assert_eq!(encoded_size(&param), file.into_inner().len() as u64);
+    let breakdown = size_breakdown(&param);
+    let mut ref_bytes = breakdown
+        .root
+        .children
+        .iter()
+        .map(|child| child.ref_bytes)
+        .collect::<Vec<_>>();
+    ref_bytes.sort_unstable();
+    assert_eq!(ref_bytes, [0, 10]);
+}
+
+#[test]
+fn test_lenient_read() {
+    let full = read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+
+    // an intact file recovers completely
+    let recovered = read_stream_lenient(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+    assert_eq!(recovered.param, full);
+    assert!(recovered.diagnostics.is_empty());
+
+    // corrupt the param number of the first hit_target entry (see the error test above)
+    let mut corrupted = FIGHTER_PIKACHU_VL.to_vec();
+    corrupted[0xd0b] = 0xff;
+    assert!(read_stream(&mut Cursor::new(&corrupted)).is_err());
+
+    let recovered = read_stream_lenient(&mut Cursor::new(&corrupted)).unwrap();
+    assert_eq!(recovered.diagnostics.len(), 1);
+    let diagnostic = &recovered.diagnostics[0];
+    assert_eq!(diagnostic.position, 0xd0b);
+    assert_eq!(
+        diagnostic.path,
+        [
+            ErrorPathPart::Hash(hash40("hit_target")),
+            ErrorPathPart::Index(0)
+        ]
+    );
+
+    let hit_target = recovered
+        .param
+        .0
+        .iter()
+        .find(|(hash, _)| *hash == hash40("hit_target"))
+        .map(|(_, param)| param);
+    // the entry is replaced, so the indices of the others don't shift
+    assert_eq!(
+        hit_target,
+        Some(&ParamKind::List(ParamList(vec![
+            ParamKind::Placeholder(Placeholder {
+                position: 0xd0b,
+                error: diagnostic.error.to_string(),
+            }),
+            ParamKind::I32(0),
+            ParamKind::I32(6)
+        ])))
+    );
+    // placeholders can't be written back
+    let err = write_stream(&mut Cursor::new(vec![]), &recovered.param).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    // or converted into typed params
+    let err =
+        FighterPikachuVl::from_param(&ParamKind::Struct(recovered.param.clone())).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidData(_)));
+    assert_eq!(err.position.unwrap(), 0xd0b);
+    let err = FighterPikachuVl::from_param_versioned(
+        &ParamKind::Struct(recovered.param.clone()),
+        Version::new(13, 0, 1),
+    )
+    .unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidData(_)));
+    assert_eq!(err.position.unwrap(), 0xd0b);
+    let others = |param: &ParamStruct| {
+        param
+            .0
+            .iter()
+            .filter(|(hash, _)| *hash != hash40("hit_target"))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(others(&recovered.param), others(&full));
+}
+
+/// Writes the struct, returning the file and the position of the root
+fn write_with_root(param: &ParamStruct) -> (Vec<u8>, usize) {
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, param).unwrap();
+    let data = file.into_inner();
+    let table_size = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    let root = 0x10 + (table_size(8) + table_size(12)) as usize;
+    (data, root)
+}
+
+#[test]
+fn test_lenient_read_damaged_offsets() {
+    // a string offset which overflows when added to the ref table position
+    let (mut data, root) = write_with_root(&ParamStruct(vec![(
+        hash40("name"),
+        ParamKind::Str("pikachu".into()),
+    )]));
+    data[root + 10..root + 14].copy_from_slice(&0xfffffff0u32.to_le_bytes());
+    assert!(read_stream(&mut Cursor::new(&data)).is_err());
+    let recovered = read_stream_lenient(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(recovered.diagnostics.len(), 1);
+    assert_eq!(recovered.diagnostics[0].position, root as u64 + 9);
+    assert!(matches!(
+        recovered.param.0[..],
+        [(hash, ParamKind::Placeholder(_))] if hash == hash40("name")
+    ));
+
+    // a list whose first child is the list itself
+    let (mut data, root) = write_with_root(&ParamStruct(vec![(
+        hash40("list"),
+        ParamKind::List(ParamList(vec![ParamKind::I32(1), ParamKind::I32(2)])),
+    )]));
+    let list = root + 9;
+    data[list + 5..list + 9].copy_from_slice(&0u32.to_le_bytes());
+    assert!(read_stream(&mut Cursor::new(&data)).is_err());
+    let recovered = read_stream_lenient(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(recovered.diagnostics.len(), 1);
+    assert_eq!(
+        recovered.diagnostics[0].path,
+        [ErrorPathPart::Hash(hash40("list")), ErrorPathPart::Index(0)]
+    );
+    match &recovered.param.0[0].1 {
+        ParamKind::List(list) => {
+            assert!(matches!(list.0[0], ParamKind::Placeholder(_)));
+            assert_eq!(list.0[1], ParamKind::I32(2));
+        }
+        _ => panic!("expected a list"),
+    }
+
+    // a struct entry whose hash index is outside of the hash table only
+    // loses that entry
+    let (mut data, root) = write_with_root(&ParamStruct(vec![
+        (hash40("a"), ParamKind::I32(1)),
+        (hash40("b"), ParamKind::I32(2)),
+    ]));
+    let ref_table = 0x10
+        + u32::from_le_bytes(data[8..12].try_into().unwrap()) as usize
+        + u32::from_le_bytes(data[root + 5..root + 9].try_into().unwrap()) as usize;
+    data[ref_table..ref_table + 4].copy_from_slice(&0xffffu32.to_le_bytes());
+    assert!(read_stream(&mut Cursor::new(&data)).is_err());
+    let recovered = read_stream_lenient(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(recovered.diagnostics.len(), 1);
+    assert!(recovered.diagnostics[0].path.is_empty());
+    assert_eq!(recovered.diagnostics[0].position, root as u64);
+    assert_eq!(recovered.param.0.len(), 1);
+}
+
+#[test]
+fn test_write_from_struct_def() {
+    let pikachu_vl = FighterPikachuVl::read_file(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+
+    let mut file = Cursor::new(vec![]);
+    pikachu_vl.write_file(&mut file).unwrap();
+    file.set_position(0);
+    assert_eq!(FighterPikachuVl::read_file(&mut file).unwrap(), pikachu_vl);
+
+    // renamed fields are written under the param's real name
+    file.set_position(0);
+    let param = read_stream(&mut file).unwrap();
+    assert!(param
+        .0
+        .iter()
+        .any(|(hash, _)| *hash == hash40("cliff_hang_data")));
+}
+
+#[test]
+fn test_write_optional_param() {
+    let present = OptionalTestStruct {
+        required_field: 0,
+        optional_field: Some(true),
+    };
+    let missing = OptionalTestStruct {
+        required_field: 1,
+        optional_field: None,
+    };
+
+    assert_eq!(
+        present.to_param().unwrap(),
+        ParamKind::Struct(ParamStruct(vec![
+            (hash40("required_field"), ParamKind::U8(0)),
+            (hash40("optional_field"), ParamKind::Bool(true)),
+        ]))
+    );
+    assert_eq!(
+        missing.to_param().unwrap(),
+        ParamKind::Struct(ParamStruct(vec![(
+            hash40("required_field"),
+            ParamKind::U8(1)
+        )]))
+    );
+
+    assert!(true.write_file(&mut Cursor::new(vec![])).is_err());
+}
+
+#[test]
+fn test_from_param() {
+    let param = ParamKind::Struct(read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap());
+    let pikachu_vl = FighterPikachuVl::from_param(&param).unwrap();
+    assert_eq!(
+        pikachu_vl,
+        FighterPikachuVl::read_file(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap()
+    );
+    assert_eq!(
+        FighterPikachuVl::from_param(&pikachu_vl.to_param().unwrap()).unwrap(),
+        pikachu_vl
+    );
+
+    let err = FighterPikachuVlTestError1::from_param(&param).unwrap_err();
+    match &err.kind {
+        ErrorKind::WrongParamNumber { expected, received } => {
+            assert_eq!(*expected, ParamNumber::I16);
+            assert_eq!(*received, ParamNumber::I32 as u8);
+        }
+        _ => panic!("Wrong error encountered"),
+    }
+    assert!(err.position.is_err());
+    assert_eq!(
+        err.path,
+        [
+            ErrorPathPart::Hash(hash40("hit_target")),
+            ErrorPathPart::Index(0)
+        ]
+    );
+
+    let err = FighterPikachuVlTestError2::from_param(&param).unwrap_err();
+    match &err.kind {
+        ErrorKind::ParamNotFound(hash) => assert_eq!(*hash, hash40("fake_name")),
+        _ => panic!("Wrong error encountered"),
+    }
+    assert_eq!(
+        err.path,
+        [
+            ErrorPathPart::Hash(hash40("cliff_hang_data")),
+            ErrorPathPart::Index(0)
+        ]
+    );
+
+    let optional = ParamKind::Struct(ParamStruct(vec![(
+        hash40("required_field"),
+        ParamKind::U8(1),
+    )]));
+    assert_eq!(
+        OptionalTestStruct::from_param(&optional).unwrap(),
+        OptionalTestStruct {
+            required_field: 1,
+            optional_field: None
+        }
+    );
+}
+
+#[derive(Debug, PartialEq)]
+struct ManualPercent(u8);
+
+impl Prc for ManualPercent {
+    fn read_param<S: ParamSource>(
+        source: &mut S,
+        ctx: &crate::prc_trait::ReadContext,
+    ) -> crate::prc_trait::Result<Self> {
+        u8::read_param(source, ctx).map(ManualPercent)
+    }
+}
+
+#[test]
+fn test_default_from_param() {
+    assert_eq!(
+        ManualPercent::from_param(&ParamKind::U8(50)).unwrap(),
+        ManualPercent(50)
+    );
+    let err = Vec::<ManualPercent>::from_param(&ParamKind::List(ParamList(vec![
+        ParamKind::U8(50),
+        ParamKind::Bool(true),
+    ])))
+    .unwrap_err();
+    assert_eq!(err.path, [ErrorPathPart::Index(1)]);
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct SerdeVl {
+    map_coll_data: Vec<SerdeMapColl>,
+    hit_target: Vec<i32>,
+    #[serde(rename = "cliff_hang_data")]
+    ledge_grab_data: Vec<BTreeMap<String, f32>>,
+    missing: Option<u8>,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct SerdeMapColl {
+    #[serde(rename = "0x04857fe845")]
+    unk: Hash40,
+    offset_x: f32,
+    offset_y: f32,
+    offset_z: f32,
+}
+
+// some params of the test file, to check they're written back the same
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct SerdeKnownVl {
+    map_coll_data: Vec<SerdeMapColl>,
+    hit_target: Vec<i32>,
+    cliff_hang_data: Vec<SerdeLedgeGrabBox>,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct SerdeLedgeGrabBox {
+    p1_x: f32,
+    p1_y: f32,
+    p2_x: f32,
+    p2_y: f32,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[allow(non_camel_case_types)]
+enum SerdeBodyPart {
+    head,
+    footl,
+    Offset { x: f32 },
+}
+
+#[test]
+fn test_serde_param() {
+    let param = ParamKind::Struct(read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap());
+    let vl: SerdeVl = crate::from_param(&param).unwrap();
+    assert_eq!(vl.hit_target, [1, 0, 6]);
+    assert_eq!(vl.map_coll_data[0].unk, hash40("head"));
+    assert_eq!(vl.map_coll_data[0].offset_x, 1.5);
+    assert_eq!(vl.ledge_grab_data[0][&hash40("p1_x").to_label()], 16.0);
+    assert_eq!(vl.missing, None);
+
+    let written = crate::to_param(&vl).unwrap();
+    assert_eq!(crate::from_param::<SerdeVl>(&written).unwrap(), vl);
+    let known_vl: SerdeKnownVl = crate::from_param(&param).unwrap();
+    let known = [
+        hash40("map_coll_data"),
+        hash40("hit_target"),
+        hash40("cliff_hang_data"),
+    ];
+    let expected = ParamStruct(
+        ParamStruct::try_from(param.clone())
+            .unwrap()
+            .0
+            .into_iter()
+            .filter(|(hash, _)| known.contains(hash))
+            .collect(),
+    );
+    assert_eq!(
+        crate::to_param(&known_vl).unwrap(),
+        ParamKind::Struct(expected)
+    );
+    let written = ParamStruct::try_from(written).unwrap();
+    assert!(!written.0.iter().any(|(hash, _)| *hash == hash40("missing")));
+    assert_eq!(
+        crate::to_param(&vl.map_coll_data[0]).unwrap(),
+        ParamKind::Struct(ParamStruct(vec![
+            (Hash40(0x04857fe845), ParamKind::Hash(hash40("head"))),
+            (hash40("offset_x"), ParamKind::Float(1.5)),
+            (hash40("offset_y"), ParamKind::Float(0.0)),
+            (hash40("offset_z"), ParamKind::Float(0.0)),
+        ]))
+    );
+
+    // params have no 64-bit numbers, so they're narrowed if they fit
+    assert_eq!(crate::to_param(&1.5f64).unwrap(), ParamKind::Float(1.5));
+    assert_eq!(crate::to_param(&-3i64).unwrap(), ParamKind::I32(-3));
+    assert_eq!(crate::to_param(&3u64).unwrap(), ParamKind::U32(3));
+    assert!(crate::to_param(&f64::MAX).is_err());
+    assert!(crate::to_param(&(i64::from(i32::MIN) - 1)).is_err());
+    assert!(crate::to_param(&(u64::from(u32::MAX) + 1)).is_err());
+    // and narrower params can be read into them
+    assert_eq!(
+        crate::from_param::<f64>(&ParamKind::Float(1.5)).unwrap(),
+        1.5
+    );
+
+    assert_eq!(
+        crate::to_param(&vec![SerdeBodyPart::head, SerdeBodyPart::Offset { x: 1.0 }]).unwrap(),
+        ParamKind::List(ParamList(vec![
+            ParamKind::Hash(hash40("head")),
+            ParamKind::Struct(ParamStruct(vec![(
+                hash40("Offset"),
+                ParamKind::Struct(ParamStruct(vec![(hash40("x"), ParamKind::Float(1.0))]))
+            )])),
+        ]))
+    );
+    let parts: Vec<SerdeBodyPart> = crate::from_param(&ParamKind::List(ParamList(vec![
+        ParamKind::Hash(hash40("footl")),
+        ParamKind::Hash(hash40("head")),
+    ])))
+    .unwrap();
+    assert_eq!(parts, [SerdeBodyPart::footl, SerdeBodyPart::head]);
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct SerdeHashes {
+    #[serde(with = "crate::serde_hash::option")]
+    target: Option<Hash40>,
+    #[serde(with = "crate::serde_hash::vec")]
+    parts: Vec<Hash40>,
+    // without an annotation, the hash is still written as a hash
+    label: Hash40,
+    labels: Vec<Option<Hash40>>,
+    keyed: BTreeMap<String, i32>,
+}
+
+#[test]
+fn test_serde_hashes() {
+    let hashes = SerdeHashes {
+        target: Some(hash40("head")),
+        parts: vec![hash40("footl"), Hash40(0x04857fe845)],
+        label: Hash40(0x04857fe845),
+        labels: vec![Some(hash40("head"))],
+        keyed: vec![("0x04857fe845".to_string(), 1), ("head".to_string(), 2)]
+            .into_iter()
+            .collect(),
+    };
+    let param = crate::to_param(&hashes).unwrap();
+    assert_eq!(
+        param,
+        ParamKind::Struct(ParamStruct(vec![
+            (hash40("target"), ParamKind::Hash(hash40("head"))),
+            (
+                hash40("parts"),
+                ParamKind::List(ParamList(vec![
+                    ParamKind::Hash(hash40("footl")),
+                    ParamKind::Hash(Hash40(0x04857fe845)),
+                ]))
+            ),
+            (hash40("label"), ParamKind::Hash(Hash40(0x04857fe845))),
+            (
+                hash40("labels"),
+                ParamKind::List(ParamList(vec![ParamKind::Hash(hash40("head"))]))
+            ),
+            // keys are hashed like field names, so hexadecimal keys are kept
+            (
+                hash40("keyed"),
+                ParamKind::Struct(ParamStruct(vec![
+                    (Hash40(0x04857fe845), ParamKind::I32(1)),
+                    (hash40("head"), ParamKind::I32(2)),
+                ]))
+            ),
+        ]))
+    );
+    let read = crate::from_param::<SerdeHashes>(&param).unwrap();
+    assert_eq!(
+        (read.target, &read.parts, read.label, &read.labels),
+        (hashes.target, &hashes.parts, hashes.label, &hashes.labels)
+    );
+    // strings stay strings
+    assert_eq!(
+        crate::to_param("head").unwrap(),
+        ParamKind::Str("head".into())
+    );
+    assert_eq!(
+        crate::to_param(&hash40("head")).unwrap(),
+        ParamKind::Hash(hash40("head"))
+    );
+    // keys are read back as labels
+    assert_eq!(read.keyed[&hash40("head").to_label()], 2);
+
+    let missing = SerdeHashes {
+        target: None,
+        parts: vec![],
+        ..hashes
+    };
+    let param = ParamStruct::try_from(crate::to_param(&missing).unwrap()).unwrap();
+    assert!(!param.0.iter().any(|(hash, _)| *hash == hash40("target")));
+}
+
+#[test]
+fn test_write_none() {
+    // like optional fields, None values leave out the param
+    let map: BTreeMap<Hash40, Option<i32>> = vec![(hash40("a"), Some(1)), (hash40("b"), None)]
+        .into_iter()
+        .collect();
+    assert_eq!(
+        map.to_param().unwrap(),
+        ParamKind::Struct(ParamStruct(vec![(hash40("a"), ParamKind::I32(1))]))
+    );
+
+    // but there's nothing to write for None in a list
+    let list = vec![Some(1), None];
+    let err = list.to_param().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+struct ContainerTestStruct {
+    point: [f32; 3],
+    pair: (Hash40, i32),
+    boxed: Box<u8>,
+    shared: std::rc::Rc<Option<u8>>,
+    keyed: std::collections::HashMap<Hash40, i32>,
+    ordered: BTreeMap<Hash40, String>,
+    indexed: indexmap::IndexMap<Hash40, bool>,
+}
+
+#[test]
+fn test_container_params() {
+    let data = ContainerTestStruct {
+        point: [1.0, 2.0, 3.0],
+        pair: (hash40("head"), 4),
+        boxed: Box::new(5),
+        shared: std::rc::Rc::new(None),
+        keyed: vec![(hash40("a"), 1), (hash40("b"), 2)]
+            .into_iter()
+            .collect(),
+        ordered: vec![(hash40("c"), "c".to_string())].into_iter().collect(),
+        indexed: vec![(hash40("d"), true), (hash40("e"), false)]
+            .into_iter()
+            .collect(),
+    };
+
+    let mut file = Cursor::new(vec![]);
+    data.write_file(&mut file).unwrap();
+    file.set_position(0);
+    assert_eq!(ContainerTestStruct::read_file(&mut file).unwrap(), data);
+    assert_eq!(
+        ContainerTestStruct::from_param(&data.to_param().unwrap()).unwrap(),
+        data
+    );
+
+    let short = ParamKind::List(ParamList(vec![ParamKind::Float(1.0)]));
+    let param = ParamStruct(vec![(hash40("point"), short)]);
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    file.set_position(0);
+
+    #[derive(Debug, Prc)]
+    #[prc(path = crate)]
+    struct PointOnly {
+        #[allow(dead_code)]
+        point: [f32; 3],
+    }
+
+    let err = PointOnly::read_file(&mut file).unwrap_err();
+    match err.kind {
+        ErrorKind::WrongListLength { expected, received } => {
+            assert_eq!(expected, 3);
+            assert_eq!(received, 1);
+        }
+        _ => panic!("Wrong error encountered"),
+    }
+    assert_eq!(err.path, [ErrorPathPart::Hash(hash40("point"))]);
+    let err = PointOnly::from_param(&ParamKind::Struct(param)).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        ErrorKind::WrongListLength {
+            expected: 3,
+            received: 1
+        }
+    ));
+}
+
+#[test]
+fn test_optional_children() {
+    #[derive(Debug, PartialEq, Prc)]
+    #[prc(path = crate)]
+    struct OptionalChildren {
+        map: std::collections::HashMap<Hash40, Option<u8>>,
+        point: [Option<u8>; 2],
+        pair: (Option<u8>, Option<i32>),
+    }
+
+    let list = |children: Vec<ParamKind>| ParamKind::List(ParamList(children));
+    let param = ParamStruct(vec![
+        (
+            hash40("map"),
+            ParamKind::Struct(ParamStruct(vec![
+                (hash40("a"), ParamKind::U8(1)),
+                (hash40("b"), ParamKind::U8(2)),
+            ])),
+        ),
+        (
+            hash40("point"),
+            list(vec![ParamKind::U8(3), ParamKind::U8(4)]),
+        ),
+        (
+            hash40("pair"),
+            list(vec![ParamKind::U8(5), ParamKind::I32(6)]),
+        ),
+    ]);
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+
+    // children outside of structs are always there, so they're read as Some
+    file.set_position(0);
+    let read = OptionalChildren::read_file(&mut file).unwrap();
+    assert_eq!(
+        read.map,
+        vec![(hash40("a"), Some(1)), (hash40("b"), Some(2))]
+            .into_iter()
+            .collect()
+    );
+    assert_eq!(read.point, [Some(3), Some(4)]);
+    assert_eq!(read.pair, (Some(5), Some(6)));
+
+    file.set_position(0);
+    let map = std::collections::HashMap::<Hash40, Option<ParamKind>>::read_file(&mut file).unwrap();
+    assert_eq!(map.len(), 3);
+
+    // a child of the wrong type is still an error
+    file.set_position(0);
+    let err = BTreeMap::<Hash40, Option<u8>>::read_file(&mut file).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::WrongParamNumber { .. }));
+
+    // params in memory are converted the same way
+    let param = ParamKind::Struct(param);
+    assert_eq!(OptionalChildren::from_param(&param).unwrap(), read);
+    let list = list(vec![ParamKind::U8(1), ParamKind::U8(2)]);
+    assert_eq!(
+        Vec::<Option<u8>>::from_param(&list).unwrap(),
+        [Some(1), Some(2)]
+    );
+    let err = Vec::<Option<i32>>::from_param(&list).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::WrongParamNumber { .. }));
+    assert_eq!(err.path, [ErrorPathPart::Index(0)]);
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+struct DynamicVl {
+    map_coll_data: ParamKind,
+    hit_target: Vec<i32>,
+    cliff_hang_data: ParamList,
+}
+
+#[test]
+fn test_dynamic_params() {
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let dynamic = DynamicVl::read_file(&mut reader).unwrap();
+    reader.set_position(0);
+    let param = read_stream(&mut reader).unwrap();
+    let param = ParamKind::Struct(param);
+
+    let (_, map_coll_data) = param
+        .try_into_ref::<ParamStruct>()
+        .unwrap()
+        .0
+        .iter()
+        .find(|(hash, _)| *hash == hash40("map_coll_data"))
+        .unwrap();
+    assert_eq!(&dynamic.map_coll_data, map_coll_data);
+    assert_eq!(dynamic.cliff_hang_data.0.len(), 1);
+    assert_eq!(DynamicVl::from_param(&param).unwrap(), dynamic);
+
+    let mut file = Cursor::new(vec![]);
+    dynamic.write_file(&mut file).unwrap();
+    file.set_position(0);
+    assert_eq!(DynamicVl::read_file(&mut file).unwrap(), dynamic);
+
+    // the typed dynamic params still check the param type
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    assert_eq!(
+        ParamKind::Struct(ParamStruct::read_file(&mut reader).unwrap()),
+        param
+    );
+    reader.set_position(0);
+    let err = ParamList::read_file(&mut reader).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        ErrorKind::WrongParamNumber {
+            expected: ParamNumber::List,
+            received: 12
+        }
+    ));
+    let err = ParamList::from_param(&param).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        ErrorKind::WrongParamNumber {
+            expected: ParamNumber::List,
+            received: 12
+        }
+    ));
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+enum BodyPart {
+    #[prc(hash = "head")]
+    Head,
+    #[prc(hash = "hip")]
+    Hip,
+    #[prc(other)]
+    Other(Hash40),
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+enum StrictBodyPart {
+    #[prc(hash = "head")]
+    Head,
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+enum HitTarget {
+    None = 0,
+    Body = 1,
+    #[prc(value = 6)]
+    Tail,
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+struct EnumMapColl {
+    #[prc(hash = 0x04857fe845)]
+    part: BodyPart,
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+struct EnumVl {
+    map_coll_data: Vec<EnumMapColl>,
+    hit_target: Vec<HitTarget>,
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+#[prc(tag = "shape")]
+enum Shape {
+    #[prc(hash = "circle")]
+    Circle { radius: f32 },
+    #[prc(hash = "rect")]
+    Rect {
+        #[prc(name = "w")]
+        width: f32,
+        #[prc(name = "h")]
+        height: f32,
+    },
+    #[prc(hash = "empty")]
+    Empty,
+}
+
+#[test]
+fn test_enum_params() {
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let vl = EnumVl::read_file(&mut reader).unwrap();
+    let parts = vl
+        .map_coll_data
+        .iter()
+        .map(|coll| &coll.part)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        parts,
+        [
+            &BodyPart::Head,
+            &BodyPart::Other(hash40("shoulderr")),
+            &BodyPart::Other(hash40("shoulderl")),
+            &BodyPart::Other(hash40("footr")),
+            &BodyPart::Other(hash40("footl")),
+            &BodyPart::Hip,
+        ]
+    );
+    assert_eq!(
+        vl.hit_target,
+        [HitTarget::Body, HitTarget::None, HitTarget::Tail]
+    );
+
+    let mut file = Cursor::new(vec![]);
+    vl.write_file(&mut file).unwrap();
+    file.set_position(0);
+    assert_eq!(EnumVl::read_file(&mut file).unwrap(), vl);
+    assert_eq!(EnumVl::from_param(&vl.to_param().unwrap()).unwrap(), vl);
+
+    let shapes = vec![
+        Shape::Circle { radius: 2.0 },
+        Shape::Rect {
+            width: 1.0,
+            height: 3.0,
+        },
+        Shape::Empty,
+    ];
+    let param = shapes.to_param().unwrap();
+    match &param {
+        ParamKind::List(list) => assert_eq!(
+            list.0[1],
+            ParamKind::Struct(ParamStruct(vec![
+                (hash40("shape"), ParamKind::Hash(hash40("rect"))),
+                (hash40("w"), ParamKind::Float(1.0)),
+                (hash40("h"), ParamKind::Float(3.0)),
+            ]))
+        ),
+        _ => panic!("Shapes should be written as a list"),
+    }
+    assert_eq!(Vec::<Shape>::from_param(&param).unwrap(), shapes);
+
+    // values without a matching variant are errors
+    let err = Vec::<StrictBodyPart>::from_param(&ParamKind::List(ParamList(vec![
+        ParamKind::Hash(hash40("head")),
+        ParamKind::Hash(hash40("hip")),
+    ])))
+    .unwrap_err();
+    assert_eq!(err.path, [ErrorPathPart::Index(1)]);
+    assert!(matches!(
+        err.kind,
+        ErrorKind::UnknownValue(ParamKind::Hash(hash)) if hash == hash40("hip")
+    ));
+
+    #[derive(Debug, Prc)]
+    #[prc(path = crate)]
+    struct StrictMapColl {
+        #[allow(dead_code)]
+        #[prc(hash = 0x04857fe845)]
+        part: StrictBodyPart,
+    }
+    #[derive(Debug, Prc)]
+    #[prc(path = crate)]
+    struct StrictVl {
+        #[allow(dead_code)]
+        map_coll_data: Vec<StrictMapColl>,
+    }
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let err = StrictVl::read_file(&mut reader).unwrap_err();
+    assert_eq!(
+        err.path,
+        [
+            ErrorPathPart::Hash(hash40("map_coll_data")),
+            ErrorPathPart::Index(1),
+            ErrorPathPart::Hash(Hash40(0x04857fe845)),
+        ]
+    );
+    assert!(matches!(
+        err.kind,
+        ErrorKind::UnknownValue(ParamKind::Hash(hash)) if hash == hash40("shoulderr")
+    ));
+
+    let unknown_shape = ParamKind::Struct(ParamStruct(vec![(
+        hash40("shape"),
+        ParamKind::Hash(hash40("triangle")),
+    )]));
+    let err = Shape::from_param(&unknown_shape).unwrap_err();
+    assert_eq!(err.path, [ErrorPathPart::Hash(hash40("shape"))]);
+    assert!(matches!(err.kind, ErrorKind::UnknownValue(_)));
+}
+
+#[derive(Debug, PartialEq, Prc)]
+#[prc(path = crate)]
+struct DefaultsVl {
+    // stored as i32 in the file
+    #[prc(coerce)]
+    hit_target: Vec<u8>,
+    #[prc(default)]
+    missing_list: Vec<u8>,
+    #[prc(name = "missing_value", default = 7)]
+    missing: u16,
+}
+
+#[derive(Debug, PartialEq, Prc)]
+#[prc(path = crate)]
+struct CoercedValue {
+    #[prc(coerce, default)]
+    value: Option<u8>,
+}
+
+#[test]
+fn test_default_and_coerced_params() {
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let vl = DefaultsVl::read_file(&mut reader).unwrap();
+    assert_eq!(
+        vl,
+        DefaultsVl {
+            hit_target: vec![1, 0, 6],
+            missing_list: vec![],
+            missing: 7,
+        }
+    );
+    reader.set_position(0);
+    let param = ParamKind::Struct(read_stream(&mut reader).unwrap());
+    assert_eq!(DefaultsVl::from_param(&param).unwrap(), vl);
+
+    // missing params inside a defaulted field are still errors
+    #[derive(Debug, Prc)]
+    #[prc(path = crate)]
+    struct MissingInner {
+        #[allow(dead_code)]
+        #[prc(name = "cliff_hang_data", default)]
+        ledge_grab_data: Vec<LedgeGrabBoxtestError2>,
+    }
+    reader.set_position(0);
+    let err = MissingInner::read_file(&mut reader).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::ParamNotFound(_)));
+    assert_eq!(err.path.len(), 2);
+
+    let mut file = Cursor::new(vec![]);
+    let out_of_range = ParamStruct(vec![(hash40("value"), ParamKind::I32(300))]);
+    write_stream(&mut file, &out_of_range).unwrap();
+    file.set_position(0);
+    let err = CoercedValue::read_file(&mut file).unwrap_err();
+    assert_eq!(err.path, [ErrorPathPart::Hash(hash40("value"))]);
+    assert!(matches!(
+        err.kind,
+        ErrorKind::OutOfRange {
+            value: 300,
+            expected: ParamNumber::U8
+        }
+    ));
+    let err = CoercedValue::from_param(&ParamKind::Struct(out_of_range)).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::OutOfRange { value: 300, .. }));
+
+    let value = ParamStruct(vec![(hash40("value"), ParamKind::I16(200))]);
+    assert_eq!(
+        CoercedValue::from_param(&ParamKind::Struct(value)).unwrap(),
+        CoercedValue { value: Some(200) }
+    );
+    let empty = ParamKind::Struct(ParamStruct(vec![]));
+    assert_eq!(
+        CoercedValue::from_param(&empty).unwrap(),
+        CoercedValue { value: None }
+    );
+}
+
+#[test]
+fn test_error_display() {
+    Hash40::label_map().lock().unwrap().add_labels(vec![
+        "cliff_hang_data".to_string(),
+        "fake_name".to_string(),
+        "hit_target".to_string(),
+    ]);
+
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let err = FighterPikachuVlTestError2::read_file(&mut reader).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "cliff_hang_data[0].fake_name: param not found at position 0x1071"
+    );
+
+    reader.set_position(0);
+    let err = FighterPikachuVlTestError1::read_file(&mut reader).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "hit_target[0]: expected i16, received i32 at position 0xd0b"
+    );
+    assert!(std::error::Error::source(&err).is_none());
+
+    // errors from the reader are kept as the source
+    let mut truncated = Cursor::new(&FIGHTER_PIKACHU_VL[..0xd0c]);
+    let err = FighterPikachuVl::read_file(&mut truncated).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::UnexpectedEof(_)));
+    assert!(err
+        .to_string()
+        .contains(": unexpected end of file at position"));
+    assert!(std::error::Error::source(&err).is_some());
+
+    let err = u8::from_param(&ParamKind::Float(1.0)).unwrap_err();
+    assert_eq!(err.to_string(), "<root>: expected u8, received float");
+}
+
+#[derive(Debug, PartialEq, Prc)]
+#[prc(path = crate)]
+struct PortedVl {
+    hit_target: Vec<i16>,
+    #[prc(name = "cliff_hang_data")]
+    ledge_grab_data: Vec<LedgeGrabBoxtestError2>,
+    new_field: u8,
+    #[prc(default = 3)]
+    defaulted: u8,
+    optional: Option<u8>,
+}
+
+#[test]
+fn test_diagnostic_read() {
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let diagnosed = PortedVl::read_file_diagnostic(&mut reader);
+    assert_eq!(
+        diagnosed.value,
+        Some(PortedVl {
+            hit_target: vec![0, 0, 0],
+            ledge_grab_data: vec![LedgeGrabBoxtestError2 {
+                p1_x: 16.0,
+                p1_y: 21.0,
+                p2_x: -11.0,
+                fake_name: 0.0,
+            }],
+            new_field: 0,
+            defaulted: 3,
+            optional: None,
+        })
+    );
+
+    let errors = diagnosed
+        .errors
+        .iter()
+        .map(|e| (e.path.clone(), &e.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(errors.len(), 5);
+    for (i, (path, kind)) in errors[..3].iter().enumerate() {
+        assert_eq!(
+            path,
+            &[
+                ErrorPathPart::Hash(hash40("hit_target")),
+                ErrorPathPart::Index(i as u32)
+            ]
+        );
+        assert!(matches!(
+            kind,
+            ErrorKind::WrongParamNumber {
+                expected: ParamNumber::I16,
+                ..
+            }
+        ));
+    }
+    assert_eq!(
+        errors[3].0,
+        [
+            ErrorPathPart::Hash(hash40("cliff_hang_data")),
+            ErrorPathPart::Index(0)
+        ]
+    );
+    assert!(matches!(errors[3].1, ErrorKind::ParamNotFound(hash) if *hash == hash40("fake_name")));
+    assert!(errors[4].0.is_empty());
+    assert!(matches!(errors[4].1, ErrorKind::ParamNotFound(hash) if *hash == hash40("new_field")));
+
+    // types without a fallback value can't be filled in
+    #[derive(Debug, Prc)]
+    #[prc(path = crate)]
+    struct MissingEnum {
+        #[allow(dead_code)]
+        part: StrictBodyPart,
+    }
+    reader.set_position(0);
+    let diagnosed = MissingEnum::read_file_diagnostic(&mut reader);
+    assert!(diagnosed.value.is_none());
+    assert_eq!(diagnosed.errors.len(), 1);
+}
+
+#[derive(Debug, Prc)]
+#[prc(path = crate)]
+struct LazyVl {
+    map_coll_data: crate::prc_trait::Lazy<Vec<MapColl>>,
+    hit_target: Vec<i32>,
+    cliff_hang_data: crate::prc_trait::Lazy<Vec<LedgeGrabBoxtestError2>>,
+}
+
+#[test]
+fn test_lazy_params() {
+    // the file isn't needed to decode, so the reader is dropped after reading
+    let vl = LazyVl::read_file(&mut Cursor::new(FIGHTER_PIKACHU_VL.to_vec())).unwrap();
+    assert_eq!(vl.hit_target, [1, 0, 6]);
+
+    let mut expected_reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let expected = FighterPikachuVl::read_file(&mut expected_reader).unwrap();
+    assert_eq!(vl.map_coll_data.decode().unwrap(), expected.map_coll_data);
+
+    // errors are only found when decoding, relative to the lazy param
+    let err = vl.cliff_hang_data.decode().unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::ParamNotFound(_)));
+    assert_eq!(err.path, [ErrorPathPart::Index(0)]);
+    assert_eq!(err.position.unwrap(), 0x1071);
+
+    let param = ParamKind::Struct(read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap());
+    let vl = LazyVl::from_param(&param).unwrap();
+    assert_eq!(vl.map_coll_data.decode().unwrap(), expected.map_coll_data);
+    let vl = LazyVl::from_param_versioned(&param, Version::new(13, 0, 1)).unwrap();
+    assert_eq!(vl.map_coll_data.decode().unwrap(), expected.map_coll_data);
+}
+
+#[test]
+fn test_lazy_param_types() {
+    #[derive(Debug, Prc)]
+    #[prc(path = crate)]
+    struct WrongLazyVl {
+        #[allow(dead_code)]
+        map_coll_data: crate::prc_trait::Lazy<String>,
+    }
+
+    // the type is checked when reading, even though the param isn't decoded
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let err = WrongLazyVl::read_file(&mut reader).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        ErrorKind::WrongParamNumber {
+            expected: ParamNumber::String,
+            received: 11,
+        }
+    ));
+    assert_eq!(err.path, [ErrorPathPart::Hash(hash40("map_coll_data"))]);
+
+    let param = ParamKind::Struct(read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap());
+    let err = WrongLazyVl::from_param(&param).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        ErrorKind::WrongParamNumber {
+            expected: ParamNumber::String,
+            received: 11,
+        }
+    ));
+}
+
+#[test]
+fn test_lazy_write() {
+    let param_struct = read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+    let map_coll_data = param_struct
+        .0
+        .iter()
+        .find(|(hash, _)| *hash == hash40("map_coll_data"))
+        .map(|(_, param)| param.clone())
+        .unwrap();
+
+    let vl = LazyVl::read_file(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+    assert_eq!(vl.map_coll_data.to_param().unwrap(), map_coll_data);
+
+    let vl = LazyVl::from_param(&ParamKind::Struct(param_struct)).unwrap();
+    assert_eq!(vl.map_coll_data.to_param().unwrap(), map_coll_data);
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+struct RestVl {
+    hit_target: Vec<i32>,
+    #[prc(rest)]
+    rest: ParamStruct,
+}
+
+#[derive(Debug, PartialEq, Prc)]
+#[prc(path = crate)]
+#[prc(deny_unknown_fields)]
+struct StrictMapColl {
+    #[prc(hash = 0x04857fe845)]
+    unk: Hash40,
+    offset_x: f32,
+    offset_y: f32,
+    offset_z: f32,
+}
+
+#[derive(Debug, PartialEq, Prc)]
+#[prc(path = crate)]
+#[prc(deny_unknown_fields)]
+struct StrictVl {
+    map_coll_data: Vec<StrictMapColl>,
+    hit_target: Vec<i32>,
+}
+
+#[test]
+fn test_unknown_params() {
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let vl = RestVl::read_file(&mut reader).unwrap();
+    let full = read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+    assert_eq!(vl.hit_target, [1, 0, 6]);
+    assert_eq!(vl.rest.0.len(), full.0.len() - 1);
+    assert!(full
+        .0
+        .iter()
+        .filter(|(hash, _)| *hash != hash40("hit_target"))
+        .eq(vl.rest.0.iter()));
+
+    let param = ParamKind::Struct(full.clone());
+    assert_eq!(RestVl::from_param(&param).unwrap(), vl);
+
+    // writing keeps every param, with the unknown ones after hit_target
+    let mut file = Cursor::new(vec![]);
+    vl.write_file(&mut file).unwrap();
+    file.set_position(0);
+    let mut written = read_stream(&mut file).unwrap();
+    let mut expected = full;
+    written.0.sort_by_key(|(hash, _)| *hash);
+    expected.0.sort_by_key(|(hash, _)| *hash);
+    assert_eq!(written, expected);
+
+    // the nested structs have no unknown params, but the root does
+    let unknown_len = expected.0.len() - 2;
+    reader.set_position(0);
+    let err = StrictVl::read_file(&mut reader).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::UnknownParam(_)));
+    assert!(err.path.is_empty());
+    assert!(err.to_string().contains(": unknown param at position"));
+    let err = StrictVl::from_param(&param).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::UnknownParam(_)));
+
+    let vl = FighterPikachuVl::from_param(&param).unwrap();
+    let (_, map_coll_data) = param
+        .try_into_ref::<ParamStruct>()
+        .unwrap()
+        .0
+        .iter()
+        .find(|(hash, _)| *hash == hash40("map_coll_data"))
+        .unwrap();
+    let map_colls = Vec::<StrictMapColl>::from_param(map_coll_data).unwrap();
+    assert_eq!(map_colls.len(), vl.map_coll_data.len());
+
+    // unknown params don't stop the struct from being read
+    reader.set_position(0);
+    let diagnosed = StrictVl::read_file_diagnostic(&mut reader);
+    assert!(diagnosed.value.is_some());
+    assert_eq!(diagnosed.errors.len(), unknown_len);
+}
+
+#[derive(Debug, PartialEq, Prc)]
+#[prc(path = crate)]
+struct NamedEntry {
+    id: Hash40,
+    name: String,
+    value: i32,
+}
+
+#[test]
+fn test_read_context() {
+    let expected = (0..100)
+        .map(|i| NamedEntry {
+            id: hash40(&format!("entry_{}", i % 10)),
+            name: format!("name_{}", i % 20),
+            value: i,
+        })
+        .collect::<Vec<_>>();
+    let entries = expected
+        .iter()
+        .map(|entry| {
+            ParamKind::Struct(ParamStruct(vec![
+                (hash40("id"), ParamKind::Hash(entry.id)),
+                (hash40("name"), ParamKind::Str(entry.name.clone())),
+                (hash40("value"), ParamKind::I32(entry.value)),
+            ]))
+        })
+        .collect();
+    let param = ParamStruct(vec![(
+        hash40("entries"),
+        ParamKind::List(ParamList(entries)),
+    )]);
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+
+    // both tables are loaded by default
+    file.set_position(0);
+    let read = BTreeMap::<Hash40, Vec<NamedEntry>>::read_file(&mut file).unwrap();
+    assert_eq!(read[&hash40("entries")], expected);
+
+    // without the ref table, it's read from the file instead
+    file.set_position(0);
+    let ctx = ReadContext::from_header(&mut file, false).unwrap();
+    assert!(ctx.hash_table().contains(&hash40("entry_3")));
+    let read = BTreeMap::<Hash40, Vec<NamedEntry>>::read_param(&mut file, &ctx).unwrap();
+    assert_eq!(read[&hash40("entries")], expected);
+
+    // hash indices outside of the table are invalid
+    let param = ParamStruct(vec![(hash40("id"), ParamKind::Hash(hash40("entry_0")))]);
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    let mut data = file.into_inner();
+    let len = data.len();
+    data[len - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+    let err = BTreeMap::<Hash40, Hash40>::read_file(&mut Cursor::new(data)).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidData(_)));
+    assert_eq!(err.path, [ErrorPathPart::Hash(hash40("id"))]);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Percent(u8);
+
+impl From<u8> for Percent {
+    fn from(value: u8) -> Self {
+        Percent(value)
+    }
+}
+
+impl From<Percent> for u8 {
+    fn from(value: Percent) -> Self {
+        value.0
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Vector3 {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+mod vector3 {
+    use super::Vector3;
+    use crate::prc_trait::{ParamSource, Prc, ReadContext, Result};
+    use crate::{ParamKind, ParamList};
+
+    pub fn read_param<S: ParamSource>(source: &mut S, ctx: &ReadContext) -> Result<Vector3> {
+        <[f32; 3]>::read_param(source, ctx).map(|[x, y, z]| Vector3 { x, y, z })
+    }
+
+    pub fn to_param(value: &Vector3) -> ParamKind {
+        let list = [value.x, value.y, value.z].map(ParamKind::Float);
+        ParamKind::List(ParamList(list.to_vec()))
+    }
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+#[prc(transparent)]
+struct Targets(Vec<i32>);
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+#[prc(transparent)]
+struct MaybeCount {
+    count: Option<i32>,
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+struct ConvertedParams {
+    #[prc(from = "u8", into = "u8")]
+    percent: Percent,
+    #[prc(try_from = "i32", into = "i32")]
+    count: u8,
+    #[prc(with = "vector3")]
+    position: Vector3,
+    targets: Targets,
+    missing: MaybeCount,
+}
+
+#[test]
+fn test_converted_params() {
+    let param = ParamStruct(vec![
+        (hash40("percent"), ParamKind::U8(50)),
+        (hash40("count"), ParamKind::I32(3)),
+        (
+            hash40("position"),
+            ParamKind::List(ParamList(vec![
+                ParamKind::Float(1.0),
+                ParamKind::Float(2.0),
+                ParamKind::Float(3.0),
+            ])),
+        ),
+        (
+            hash40("targets"),
+            ParamKind::List(ParamList(vec![ParamKind::I32(1), ParamKind::I32(6)])),
+        ),
+    ]);
+    let expected = ConvertedParams {
+        percent: Percent(50),
+        count: 3,
+        position: Vector3 {
+            x: 1.0,
+            y: 2.0,
+            z: 3.0,
+        },
+        targets: Targets(vec![1, 6]),
+        missing: MaybeCount { count: None },
+    };
+
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    file.set_position(0);
+    assert_eq!(ConvertedParams::read_file(&mut file).unwrap(), expected);
+    let in_memory = ParamKind::Struct(param.clone());
+    assert_eq!(ConvertedParams::from_param(&in_memory).unwrap(), expected);
+    assert_eq!(expected.to_param().unwrap(), in_memory);
+    assert_eq!(
+        Targets::from_param(&ParamKind::List(ParamList(vec![]))).unwrap(),
+        Targets(vec![])
+    );
+
+    // the failed conversion is reported at the field
+    let mut param = param;
+    param.0[1].1 = ParamKind::I32(300);
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    file.set_position(0);
+    let err = ConvertedParams::read_file(&mut file).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Conversion(_)));
+    assert_eq!(err.path, [ErrorPathPart::Hash(hash40("count"))]);
+    assert!(err.position.is_ok());
+    let err = ConvertedParams::from_param(&ParamKind::Struct(param)).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::Conversion(_)));
+    assert_eq!(err.path, [ErrorPathPart::Hash(hash40("count"))]);
+
+    file.set_position(0);
+    let diagnosed = ConvertedParams::read_file_diagnostic(&mut file);
+    assert!(diagnosed.value.is_none());
+    assert_eq!(diagnosed.errors.len(), 1);
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+struct CharaEntry {
+    ui_chara_id: Hash40,
+    color_num: u8,
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+struct CharaDb {
+    #[prc(key = "ui_chara_id")]
+    db_root: indexmap::IndexMap<Hash40, CharaEntry>,
+}
+
+#[test]
+fn test_keyed_list() {
+    let chara_param = |name: &str, color_num: u8| {
+        ParamKind::Struct(ParamStruct(vec![
+            (hash40("ui_chara_id"), ParamKind::Hash(hash40(name))),
+            (hash40("color_num"), ParamKind::U8(color_num)),
+        ]))
+    };
+    let list = vec![
+        chara_param("ui_chara_mario", 8),
+        chara_param("ui_chara_pikachu", 8),
+        chara_param("ui_chara_eflame_only", 1),
+    ];
+    let param = ParamStruct(vec![(hash40("db_root"), ParamKind::List(ParamList(list)))]);
+
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    file.set_position(0);
+    let db = CharaDb::read_file(&mut file).unwrap();
+    let ids = db.db_root.keys().copied().collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        [
+            hash40("ui_chara_mario"),
+            hash40("ui_chara_pikachu"),
+            hash40("ui_chara_eflame_only")
+        ]
+    );
+    assert_eq!(db.db_root[&hash40("ui_chara_eflame_only")].color_num, 1);
+    let in_memory = ParamKind::Struct(param.clone());
+    assert_eq!(CharaDb::from_param(&in_memory).unwrap(), db);
+    assert_eq!(db.to_param().unwrap(), in_memory);
+
+    // the second use of a key is reported at its index
+    let mut param = param;
+    if let ParamKind::List(list) = &mut param.0[0].1 {
+        list.0.push(chara_param("ui_chara_pikachu", 4));
+    }
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    file.set_position(0);
+    let err = CharaDb::read_file(&mut file).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        ErrorKind::DuplicateKey { key, first_index: 1 } if key == hash40("ui_chara_pikachu")
+    ));
+    assert_eq!(
+        err.path,
+        [
+            ErrorPathPart::Hash(hash40("db_root")),
+            ErrorPathPart::Index(3)
+        ]
+    );
+    assert!(err.position.is_ok());
+    let err = CharaDb::from_param(&ParamKind::Struct(param.clone())).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::DuplicateKey { .. }));
+    assert_eq!(
+        err.path,
+        [
+            ErrorPathPart::Hash(hash40("db_root")),
+            ErrorPathPart::Index(3)
+        ]
+    );
+
+    // diagnostic reads keep the first struct with the key
+    file.set_position(0);
+    let diagnosed = CharaDb::read_file_diagnostic(&mut file);
+    assert_eq!(diagnosed.value.unwrap(), db);
+    assert_eq!(diagnosed.errors.len(), 1);
+
+    // and only drop the structs which fail
+    if let ParamKind::List(list) = &mut param.0[0].1 {
+        list.0[1] = ParamKind::Struct(ParamStruct(vec![(hash40("color_num"), ParamKind::U8(8))]));
+        list.0.pop();
+    }
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    file.set_position(0);
+    let diagnosed = CharaDb::read_file_diagnostic(&mut file);
+    let ids = diagnosed
+        .value
+        .unwrap()
+        .db_root
+        .into_keys()
+        .collect::<Vec<_>>();
+    assert_eq!(
+        ids,
+        [hash40("ui_chara_mario"), hash40("ui_chara_eflame_only")]
+    );
+    assert_eq!(diagnosed.errors.len(), 1);
+    assert!(matches!(
+        diagnosed.errors[0].kind,
+        ErrorKind::ParamNotFound(hash) if hash == hash40("ui_chara_id")
+    ));
+    assert_eq!(
+        diagnosed.errors[0].path,
+        [
+            ErrorPathPart::Hash(hash40("db_root")),
+            ErrorPathPart::Index(1)
+        ]
+    );
+}
+
+fn check_even(value: &i32) -> Result<(), String> {
+    if value % 2 == 0 {
+        Ok(())
+    } else {
+        Err(format!("{} is odd", value))
+    }
+}
+
+#[derive(Debug, PartialEq, Prc)]
+#[prc(path = crate)]
+struct ValidatedParams {
+    #[prc(range = 0.0..=1.0)]
+    ratio: f32,
+    #[prc(len = 3)]
+    hit_target: Vec<i32>,
+    #[prc(validate = "check_even", range = 0..)]
+    count: i32,
+}
+
+#[test]
+fn test_validated_params() {
+    let param = ParamStruct(vec![
+        (hash40("ratio"), ParamKind::Float(0.5)),
+        (
+            hash40("hit_target"),
+            ParamKind::List(ParamList(vec![
+                ParamKind::I32(1),
+                ParamKind::I32(0),
+                ParamKind::I32(6),
+            ])),
+        ),
+        (hash40("count"), ParamKind::I32(4)),
+    ]);
+    let expected = ValidatedParams {
+        ratio: 0.5,
+        hit_target: vec![1, 0, 6],
+        count: 4,
+    };
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    file.set_position(0);
+    assert_eq!(ValidatedParams::read_file(&mut file).unwrap(), expected);
+    assert_eq!(
+        ValidatedParams::from_param(&ParamKind::Struct(param.clone())).unwrap(),
+        expected
+    );
+
+    let invalid = |index: usize, value: ParamKind| {
+        let mut param = param.clone();
+        param.0[index].1 = value;
+        let mut file = Cursor::new(vec![]);
+        write_stream(&mut file, &param).unwrap();
+        file.set_position(0);
+        let err = ValidatedParams::read_file(&mut file).unwrap_err();
+        assert_eq!(err.path, [ErrorPathPart::Hash(param.0[index].0)]);
+        assert!(err.position.is_ok());
+        let in_memory = ValidatedParams::from_param(&ParamKind::Struct(param)).unwrap_err();
+        assert_eq!(in_memory.path, err.path);
+        file.set_position(0);
+        let diagnosed = ValidatedParams::read_file_diagnostic(&mut file);
+        assert!(diagnosed.value.is_some());
+        assert_eq!(diagnosed.errors.len(), 1);
+        err
+    };
+
+    let err = invalid(0, ParamKind::Float(1.5));
+    assert!(matches!(err.kind, ErrorKind::NotInRange { .. }));
+    assert!(err
+        .to_string()
+        .contains(": value 1.5 is outside of range 0.0..=1.0 at position"));
+    let err = invalid(1, ParamKind::List(ParamList(vec![])));
+    assert!(matches!(
+        err.kind,
+        ErrorKind::InvalidLength {
+            expected: 3,
+            received: 0
+        }
+    ));
+    let err = invalid(2, ParamKind::I32(3));
+    assert!(matches!(err.kind, ErrorKind::Validation(ref message) if message == "3 is odd"));
+    // checks run in order, so the range is checked first
+    let err = invalid(2, ParamKind::I32(-3));
+    assert!(matches!(err.kind, ErrorKind::NotInRange { .. }));
+}
+
+#[test]
+fn test_layered_read() {
+    use ErrorPathPart::{Hash, Index};
+
+    let base = read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+    let hit_target = ParamStruct(vec![(
+        hash40("hit_target"),
+        ParamKind::List(ParamList(vec![ParamKind::I32(1), ParamKind::I32(5)])),
+    )]);
+    let offset_x = ParamStruct(vec![(
+        hash40("map_coll_data"),
+        ParamKind::List(ParamList(vec![ParamKind::Struct(ParamStruct(vec![(
+            hash40("offset_x"),
+            ParamKind::Float(10.0),
+        )]))])),
+    )]);
+    let patches = [hit_target, offset_x];
+
+    let layered = FighterPikachuVl::from_layers(&base, &patches).unwrap();
+    let vanilla = FighterPikachuVl::read_file(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+    assert_eq!(layered.value.hit_target, [1, 5, 6]);
+    assert_eq!(layered.value.map_coll_data[0].offset_x, 10.0);
+    assert_eq!(
+        layered.value.map_coll_data[0].offset_y,
+        vanilla.map_coll_data[0].offset_y
+    );
+    assert_eq!(layered.value.ledge_grab_data, vanilla.ledge_grab_data);
+
+    let provenance = &layered.provenance;
+    assert_eq!(provenance.layer(&[]), Some(2));
+    assert_eq!(provenance.layer(&[Hash(hash40("hit_target"))]), Some(1));
+    assert_eq!(
+        provenance.layer(&[Hash(hash40("hit_target")), Index(0)]),
+        Some(1)
+    );
+    assert_eq!(
+        provenance.layer(&[Hash(hash40("hit_target")), Index(2)]),
+        Some(0)
+    );
+    let map_coll = [Hash(hash40("map_coll_data")), Index(0)];
+    assert_eq!(provenance.layer(&map_coll), Some(2));
+    assert_eq!(
+        provenance.layer(&[map_coll[0], map_coll[1], Hash(hash40("offset_x"))]),
+        Some(2)
+    );
+    assert_eq!(
+        provenance.layer(&[map_coll[0], map_coll[1], Hash(hash40("offset_y"))]),
+        Some(0)
+    );
+    assert_eq!(provenance.layer(&[Hash(hash40("missing"))]), None);
+
+    // reading the files gives the same result, even from different readers
+    let mut files = patches
+        .iter()
+        .map(|patch| {
+            let mut file = Cursor::new(vec![]);
+            write_stream(&mut file, patch).unwrap();
+            file.set_position(0);
+            file
+        })
+        .collect::<Vec<_>>();
+    let mut base_file = Cursor::new(FIGHTER_PIKACHU_VL);
+    let mut readers = files
+        .iter_mut()
+        .map(|file| file as &mut dyn ReadSeek)
+        .collect::<Vec<_>>();
+    let read = FighterPikachuVl::read_layered(&mut base_file, &mut readers).unwrap();
+    assert_eq!(read.value, layered.value);
+
+    // errors in the merged params have the position in the layer's file
+    let wrong_type = ParamStruct(vec![
+        (hash40("hit_target"), ParamKind::I32(0)),
+        (hash40("unused"), ParamKind::Bool(true)),
+    ]);
+    let mut wrong_file = Cursor::new(vec![]);
+    write_stream(&mut wrong_file, &wrong_type).unwrap();
+    wrong_file.set_position(0);
+    let ctx = prepare(&mut wrong_file).unwrap();
+    let (_, expected_position) = StructData::from_stream(&mut wrong_file)
+        .unwrap()
+        .entries(&mut wrong_file, &ctx)
+        .unwrap()
+        .into_iter()
+        .find(|(hash, _)| *hash == hash40("hit_target"))
+        .unwrap();
+
+    wrong_file.set_position(0);
+    base_file.set_position(0);
+    let err = FighterPikachuVl::read_layered(&mut base_file, &mut [&mut wrong_file]).unwrap_err();
+    assert_eq!(err.layer, 1);
+    assert!(matches!(
+        err.error.kind,
+        ErrorKind::WrongParamNumber {
+            expected: ParamNumber::List,
+            ..
+        }
+    ));
+    assert_eq!(err.error.path, [Hash(hash40("hit_target"))]);
+    assert_eq!(err.error.position.unwrap(), expected_position);
+
+    // errors reading a file are reported for its layer
+    let mut truncated = Cursor::new(&FIGHTER_PIKACHU_VL[..16]);
+    base_file.set_position(0);
+    let err = FighterPikachuVl::read_layered(&mut base_file, &mut [&mut truncated]).unwrap_err();
+    assert_eq!(err.layer, 1);
+
+    // a patch replacing a list forgets where the old children came from
+    let replaced = ParamStruct(vec![(hash40("hit_target"), ParamKind::I32(0))]);
+    let layered = ParamKind::from_layers(&base, &[replaced]).unwrap();
+    assert_eq!(
+        layered.provenance.layer(&[Hash(hash40("hit_target"))]),
+        Some(1)
+    );
+    assert_eq!(
+        layered
+            .provenance
+            .layer(&[Hash(hash40("hit_target")), Index(0)]),
+        None
+    );
+}
+
+#[derive(Debug, PartialEq, PrcBorrow)]
+#[prc(path = crate)]
+struct BorrowedEntry<'a> {
+    id: Hash40,
+    #[prc(borrow)]
+    name: &'a str,
+    value: i32,
+}
+
+#[derive(Debug, PrcBorrow)]
+#[prc(path = crate)]
+struct BorrowedEntries<'a> {
+    #[prc(borrow)]
+    entries: BorrowedList<'a, BorrowedEntry<'a>>,
+    #[prc(borrow)]
+    missing: Option<&'a str>,
+}
+
+#[derive(Debug, PrcBorrow)]
+#[prc(path = crate)]
+struct BorrowedNames<'a> {
+    #[prc(borrow, name = "entries")]
+    names: BorrowedList<'a, &'a str>,
+}
+
+fn borrowed_entries_file() -> Vec<u8> {
+    let entries = (0..10)
+        .map(|i| {
+            ParamKind::Struct(ParamStruct(vec![
+                (
+                    hash40("id"),
+                    ParamKind::Hash(hash40(&format!("entry_{}", i))),
+                ),
+                (hash40("name"), ParamKind::Str(format!("name_{}", i))),
+                (hash40("value"), ParamKind::I32(i)),
+            ]))
+        })
+        .collect();
+    let param = ParamStruct(vec![(
+        hash40("entries"),
+        ParamKind::List(ParamList(entries)),
+    )]);
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &param).unwrap();
+    file.into_inner()
+}
+
+#[test]
+fn test_borrowed_params() {
+    let data = borrowed_entries_file();
+    let borrowed = BorrowedEntries::borrow_file(&data).unwrap();
+    assert_eq!(borrowed.entries.len(), 10);
+    assert_eq!(borrowed.missing, None);
+    let entry = borrowed.entries.get(3).unwrap().unwrap();
+    assert_eq!(
+        entry,
+        BorrowedEntry {
+            id: hash40("entry_3"),
+            name: "name_3",
+            value: 3,
+        }
+    );
+    // the string points into the file instead of being copied
+    assert!(data.as_ptr_range().contains(&entry.name.as_ptr()));
+    assert!(borrowed.entries.get(10).is_none());
+    let values = borrowed
+        .entries
+        .iter()
+        .map(|entry| entry.unwrap().value)
+        .collect::<Vec<_>>();
+    assert_eq!(values, (0..10).collect::<Vec<_>>());
+    let entries = Vec::<BorrowedEntry>::borrow_file(&data[..]);
+    assert!(matches!(
+        entries.unwrap_err().kind,
+        ErrorKind::WrongParamNumber { .. }
+    ));
+
+    // errors from the children are relative to the list
+    let names = BorrowedNames::borrow_file(&data).unwrap();
+    let err = names.names.get(2).unwrap().unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::WrongParamNumber { .. }));
+    assert_eq!(err.path, [ErrorPathPart::Index(2)]);
+}
+
+#[test]
+fn test_string_encoding() {
+    #[derive(Debug, Prc)]
+    #[prc(path = crate)]
+    struct Named {
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    #[derive(Debug, PrcBorrow)]
+    #[prc(path = crate)]
+    struct BorrowedName<'a> {
+        #[prc(borrow)]
+        name: &'a str,
+    }
+
+    let name = "ピカチュウ";
+    let param_struct = ParamStruct(vec![(hash40("name"), ParamKind::Str(name.into()))]);
+    let mut writer = Cursor::new(vec![]);
+    write_stream(&mut writer, &param_struct).unwrap();
+    let mut data = writer.into_inner();
+
+    // every way of reading strings decodes UTF-8
+    assert_eq!(read_stream(&mut Cursor::new(&data)).unwrap(), param_struct);
+    assert_eq!(
+        Named::read_file(&mut Cursor::new(&data)).unwrap().name,
+        name
+    );
+    assert_eq!(BorrowedName::borrow_file(&data).unwrap().name, name);
+
+    let start = data
+        .windows(name.len())
+        .position(|window| window == name.as_bytes())
+        .unwrap();
+    data[start] = 0xff;
+
+    // strings which aren't UTF-8 are still read, with one character per byte
+    let decoded = data[start..start + name.len()]
+        .iter()
+        .map(|byte| *byte as char)
+        .collect::<String>();
+    let read = read_stream(&mut Cursor::new(&data)).unwrap();
+    assert_eq!(read.0[0].1, ParamKind::Str(decoded.clone()));
+    assert_eq!(
+        Named::read_file(&mut Cursor::new(&data)).unwrap().name,
+        decoded
+    );
+    // but they can't be borrowed
+    let err = BorrowedName::borrow_file(&data).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::InvalidData(_)));
+}
+
+fn is_first_patch(version: Version) -> bool {
+    version.patch == 1
+}
+
+#[derive(Debug, PartialEq, Prc)]
+#[prc(path = crate)]
+struct VersionedVl {
+    hit_target: Vec<i32>,
+    #[prc(since = "13.0")]
+    added_param: i32,
+    #[prc(name = "hit_target", until = "2.0.0")]
+    old_targets: Vec<i32>,
+    #[prc(name = "hit_target", if = "is_first_patch", default = vec![-1])]
+    patch_targets: Vec<i32>,
+}
+
+#[test]
+fn test_versioned_fields() {
+    let read = |version: Option<&str>| {
+        let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+        let mut ctx = prepare(&mut reader).unwrap();
+        if let Some(version) = version {
+            ctx = ctx.with_version(version.parse().unwrap());
+        }
+        VersionedVl::read_param(&mut reader, &ctx)
+    };
+
+    let vl = read(Some("1.1.0")).unwrap();
+    assert_eq!(vl.added_param, 0);
+    assert_eq!(vl.old_targets, [1, 0, 6]);
+    assert_eq!(vl.patch_targets, [-1]);
+    let vl = read(Some("12.0.1")).unwrap();
+    assert!(vl.old_targets.is_empty());
+    assert_eq!(vl.patch_targets, [1, 0, 6]);
+
+    // without a version, params are read if they're in the file
+    let vl = read(None).unwrap();
+    assert_eq!(vl.added_param, 0);
+    assert_eq!(vl.old_targets, [1, 0, 6]);
+    assert_eq!(vl.patch_targets, [1, 0, 6]);
+    let param = ParamKind::Struct(read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap());
+    assert_eq!(VersionedVl::from_param(&param).unwrap(), vl);
+
+    // params required for the version must be in the file
+    let err = read(Some("13.0.1")).unwrap_err();
+    let version = Version::new(13, 0, 1);
+    assert!(matches!(
+        err.kind,
+        ErrorKind::MissingForVersion { hash, version: v } if hash == hash40("added_param") && v == version
+    ));
+    assert!(err.path.is_empty());
+    assert!(err
+        .to_string()
+        .contains(": param not found, but required for version 13.0.1 at position"));
+
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let err = VersionedVl::read_file_versioned(&mut reader, version).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::MissingForVersion { .. }));
+    assert!(err.position.is_ok());
+    let err = VersionedVl::from_param_versioned(&param, version).unwrap_err();
+    assert!(matches!(
+        err.kind,
+        ErrorKind::MissingForVersion { hash, version: v } if hash == hash40("added_param") && v == version
+    ));
+    assert!(err.path.is_empty());
+    assert!(err.position.is_err());
+    let vl = VersionedVl::from_param_versioned(&param, Version::new(12, 0, 1)).unwrap();
+    assert!(vl.old_targets.is_empty());
+    assert_eq!(vl.patch_targets, [1, 0, 6]);
+
+    reader.set_position(0);
+    let ctx = prepare(&mut reader).unwrap().with_version(version);
+    let mut errors = Vec::new();
+    let vl = VersionedVl::read_param_diagnostic(&mut reader, &ctx, &mut errors);
+    assert_eq!(vl.unwrap().old_targets, Vec::<i32>::new());
+    assert_eq!(errors.len(), 1);
+    assert!(matches!(
+        errors[0].kind,
+        ErrorKind::MissingForVersion { .. }
+    ));
+
+    assert_eq!("13".parse(), Ok(Version::new(13, 0, 0)));
+    assert!("13.0.1.2".parse::<Version>().is_err());
+    assert!("13.x".parse::<Version>().is_err());
+    assert!("13..1".parse::<Version>().is_err());
+    assert!("99999999999".parse::<Version>().is_err());
+    assert_eq!(Version::parse("13.0.1"), Some(version));
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+#[prc(rename_all = "lowercase")]
+enum RenamedBodyPart {
+    Head,
+    ShoulderR,
+    ShoulderL,
+    FootR,
+    FootL,
+    Hip,
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+enum SnakeCaseBodyPart {
+    Head,
+    ShoulderR,
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+#[prc(prefix = "offset_")]
+#[prc(deny_unknown_fields)]
+struct PrefixedMapColl {
+    #[prc(hash = 0x04857fe845)]
+    part: RenamedBodyPart,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+#[derive(Debug, PartialEq, Prc, PrcWrite)]
+#[prc(path = crate)]
+struct AliasedVl {
+    map_coll_data: Vec<PrefixedMapColl>,
+    // a newer name is written, while the older one is still read
+    #[prc(alias = "hit_target")]
+    hit_targets: Vec<i32>,
+    #[prc(alias = "ledge_data", alias = "cliff_hang_data")]
+    ledge_grab_data: Vec<LedgeGrabBox>,
+    #[prc(rest)]
+    rest: ParamStruct,
+}
+
+#[derive(Debug, Prc)]
+#[prc(path = crate)]
+struct MissingAliasVl {
+    #[allow(dead_code)]
+    #[prc(alias = "old_target", alias = "older_target")]
+    target: Vec<i32>,
+}
+
+#[derive(PrcWrite)]
+#[prc(path = crate)]
+#[prc(rename_all = "camelCase")]
+struct CamelCaseParams {
+    hit_target: i32,
+    p1_x: f32,
+}
+
+#[test]
+fn test_renamed_params() {
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let vl = AliasedVl::read_file(&mut reader).unwrap();
+    assert_eq!(vl.map_coll_data[0].part, RenamedBodyPart::Head);
+    assert_eq!(vl.map_coll_data[0].x, 1.5);
+    assert_eq!(vl.map_coll_data[1].part, RenamedBodyPart::ShoulderR);
+    assert_eq!(vl.map_coll_data[5].part, RenamedBodyPart::Hip);
+    assert_eq!(vl.hit_targets, [1, 0, 6]);
+    assert_eq!(vl.ledge_grab_data[0].p1_x, 16.0);
+    assert!(!vl
+        .rest
+        .0
+        .iter()
+        .any(|(hash, _)| { [hash40("hit_target"), hash40("cliff_hang_data")].contains(hash) }));
+    let param = ParamKind::Struct(read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap());
+    assert_eq!(AliasedVl::from_param(&param).unwrap(), vl);
+
+    // the field's own name is preferred, and written
+    let param = vl.to_param().unwrap();
+    let hashes = match &param {
+        ParamKind::Struct(param_struct) => param_struct.0.iter().map(|(hash, _)| *hash),
+        _ => panic!("expected a struct"),
+    };
+    assert_eq!(
+        hashes.take(3).collect::<Vec<_>>(),
+        [
+            hash40("map_coll_data"),
+            hash40("hit_targets"),
+            hash40("ledge_grab_data")
+        ]
+    );
+    assert_eq!(AliasedVl::from_param(&param).unwrap(), vl);
+
+    // with none of the names present, the field's own is missing
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    let err = MissingAliasVl::read_file(&mut reader).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::ParamNotFound(hash) if hash == hash40("target")));
+    let err = MissingAliasVl::from_param(&param).unwrap_err();
+    assert!(matches!(err.kind, ErrorKind::ParamNotFound(hash) if hash == hash40("target")));
+
+    let params = CamelCaseParams {
+        hit_target: 1,
+        p1_x: 0.0,
+    };
+    let hashes = match params.to_param().unwrap() {
+        ParamKind::Struct(param_struct) => param_struct.0.into_iter().map(|(hash, _)| hash),
+        _ => panic!("expected a struct"),
+    };
+    assert_eq!(
+        hashes.collect::<Vec<_>>(),
+        [hash40("hitTarget"), hash40("p1X")]
+    );
+
+    // variants are labeled in snake_case by default
+    let param = ParamKind::Hash(hash40("shoulder_r"));
+    assert_eq!(
+        SnakeCaseBodyPart::from_param(&param).unwrap(),
+        SnakeCaseBodyPart::ShoulderR
+    );
+    assert_eq!(SnakeCaseBodyPart::ShoulderR.to_param().unwrap(), param);
+    assert_eq!(
+        SnakeCaseBodyPart::Head.to_param().unwrap(),
+        ParamKind::Hash(hash40("head"))
+    );
+}
+
+/// Reads T from every kind of source of the test file
+fn read_from_sources<T: Prc>() -> Vec<crate::prc_trait::Result<T>> {
+    let param_struct = read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+    #[cfg(feature = "xml-feat")]
+    let mut xml = Vec::new();
+    #[cfg(feature = "xml-feat")]
+    crate::xml::write_xml(&param_struct, &mut xml).unwrap();
+
+    vec![
+        T::read_source(&mut Cursor::new(FIGHTER_PIKACHU_VL)),
+        T::read_source(FIGHTER_PIKACHU_VL),
+        T::read_source(&ParamKind::Struct(param_struct)),
+        #[cfg(feature = "xml-feat")]
+        T::read_source(crate::prc_trait::Xml(&xml[..])),
+    ]
+}
+
+#[test]
+fn test_sources_match() {
+    let expected = FighterPikachuVl::read_file(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+    for value in read_from_sources::<FighterPikachuVl>() {
+        assert_eq!(value.unwrap(), expected);
+    }
+    // lazy params decode the same, wherever they're kept
+    for value in read_from_sources::<LazyVl>() {
+        let value = value.unwrap();
+        assert_eq!(value.hit_target, expected.hit_target);
+        assert_eq!(
+            value.map_coll_data.decode().unwrap(),
+            expected.map_coll_data
+        );
+    }
+
+    // errors have the same kind and path, but positions differ
+    fn check_errors<T: Prc + std::fmt::Debug>() {
+        let errors = read_from_sources::<T>()
+            .into_iter()
+            .map(|value| value.unwrap_err())
+            .collect::<Vec<_>>();
+        for err in &errors[1..] {
+            assert_eq!(err.path, errors[0].path);
+            assert_eq!(err.kind.to_string(), errors[0].kind.to_string());
+        }
+    }
+    check_errors::<FighterPikachuVlTestError1>();
+    check_errors::<FighterPikachuVlTestError2>();
+    check_errors::<WrongHitTarget>();
+}
+
+#[derive(Debug, Prc)]
+#[prc(path = crate)]
+struct WrongHitTarget {
+    #[allow(dead_code)]
+    #[prc(name = "hit_target")]
+    target: [i32; 2],
+}
+
+#[test]
+fn test_param_sources() {
+    let param = ParamKind::Struct(read_stream(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap());
+    let expected = FighterPikachuVl::read_file(&mut Cursor::new(FIGHTER_PIKACHU_VL)).unwrap();
+    let mut reader = Cursor::new(FIGHTER_PIKACHU_VL);
+    assert_eq!(
+        FighterPikachuVl::read_source(&mut reader).unwrap(),
+        expected
+    );
+    assert_eq!(
+        FighterPikachuVl::read_source(FIGHTER_PIKACHU_VL).unwrap(),
+        expected
+    );
+    assert_eq!(FighterPikachuVl::read_source(&param).unwrap(), expected);
+
+    // only the binary source has a position
+    let expected_path = [
+        ErrorPathPart::Hash(hash40("hit_target")),
+        ErrorPathPart::Index(0),
+    ];
+    let err = FIGHTER_PIKACHU_VL
+        .read_prc::<FighterPikachuVlTestError1>()
+        .unwrap_err();
+    assert_eq!(err.path, expected_path);
+    assert_eq!(err.position.unwrap(), 0xd0b);
+    let err = (&param)
+        .read_prc::<FighterPikachuVlTestError1>()
+        .unwrap_err();
+    assert_eq!(err.path, expected_path);
+    assert!(err.position.is_err());
+
+    // fields only read for some versions are handled the same way
+    let version = Version::new(12, 0, 1);
+    assert_eq!(
+        VersionedVl::read_source_versioned(FIGHTER_PIKACHU_VL, version).unwrap(),
+        VersionedVl::read_source_versioned(&param, version).unwrap()
+    );
+
+    // with duplicate hashes, every source reads the first param
+    #[derive(Debug, PartialEq, Prc)]
+    #[prc(path = crate)]
+    struct Duplicated {
+        a: i32,
+    }
+    let duplicated = ParamStruct(vec![
+        (hash40("a"), ParamKind::I32(1)),
+        (hash40("a"), ParamKind::I32(2)),
+    ]);
+    let mut file = Cursor::new(vec![]);
+    write_stream(&mut file, &duplicated).unwrap();
+    let file = file.into_inner();
+    assert_eq!(read_stream(&mut Cursor::new(&file)).unwrap(), duplicated);
+    let first = Duplicated { a: 1 };
+    assert_eq!(Duplicated::read_source(&file[..]).unwrap(), first);
+    let duplicated = ParamKind::Struct(duplicated);
+    assert_eq!(Duplicated::read_source(&duplicated).unwrap(), first);
+
+    #[cfg(feature = "xml-feat")]
+    {
+        use crate::prc_trait::Xml;
+
+        let mut xml = Vec::new();
+        match &param {
+            ParamKind::Struct(param_struct) => {
+                crate::xml::write_xml(param_struct, &mut xml).unwrap()
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(
+            FighterPikachuVl::read_source(Xml(&xml[..])).unwrap(),
+            expected
+        );
+        let err = Xml(&xml[..])
+            .read_prc::<FighterPikachuVlTestError1>()
+            .unwrap_err();
+        assert_eq!(err.path, expected_path);
+        assert!(err.position.is_err());
+
+        let err = FighterPikachuVl::read_source(Xml(&b"<struct><list>"[..])).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Xml(_)));
+        assert!(err.position.is_ok());
+        assert_eq!(
+            err.to_string(),
+            "<root>: invalid xml: struct child is missing the 'hash' attribute at position 0x8"
+        );
+    }
+}
+
+// the derives qualify every trait, so they work without importing them
+mod without_imports {
+    #[derive(Debug, PartialEq, crate::Prc, crate::PrcWrite)]
+    #[prc(path = crate)]
+    pub struct Point {
+        pub x: f32,
+        pub y: f32,
+    }
+}
+
+#[test]
+fn test_derive_without_imports() {
+    let point = without_imports::Point { x: 1.0, y: 2.0 };
+    let param = point.to_param().unwrap();
+    assert_eq!(without_imports::Point::from_param(&param).unwrap(), point);
+}
@@ -3,7 +3,8 @@ use quick_xml::events::attributes::Attributes;
 use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::{Reader, Writer};
 
-use std::io::{BufRead, Error as ioError, Read, Write};
+use std::fmt;
+use std::io::{BufRead, BufReader, Error as ioError, Read, Write};
 use std::str::{from_utf8, FromStr, Utf8Error};
 
 pub use quick_xml;
@@ -51,6 +52,9 @@ fn param_to_node<W: Write>(
         ParamKind::Str(val) => write_constant!(b"string", val),
         ParamKind::List(val) => list_to_node(val, writer, attr)?,
         ParamKind::Struct(val) => struct_to_node(val, writer, attr)?,
+        ParamKind::Placeholder(val) => {
+            return Err(quick_xml::Error::Io(val.write_error()));
+        }
     };
 
     Ok(())
@@ -140,7 +144,7 @@ pub fn get_xml_error<R: Read>(reader: R, start: usize, end: usize) -> Result<Str
     let mut line_start = 0;
     let mut line_num = 1;
 
-    for (position, byte_res) in reader.bytes().enumerate() {
+    for (position, byte_res) in BufReader::new(reader).bytes().enumerate() {
         let byte = byte_res?;
         match stage {
             Stage::One => {
@@ -239,6 +243,36 @@ pub enum ReadError {
     UnhandledEvent(QuickXmlEventType),
 }
 
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::QuickXml(e) => write!(f, "{}", e),
+            Self::ParseError => f.write_str("couldn't parse the value of a param"),
+            Self::UnknownOpenTag(tag) => write!(f, "unknown tag '{}'", tag),
+            Self::UnmatchedCloseTag(tag) => {
+                write!(f, "close tag '{}' doesn't match the open tag", tag)
+            }
+            Self::MissingHash => f.write_str("struct child is missing the 'hash' attribute"),
+            Self::ExpectedStructTag => f.write_str("expected the root 'struct' tag"),
+            Self::ExpectedOpenOrCloseTag(tag) => {
+                write!(f, "expected an open tag or the close tag of '{}'", tag)
+            }
+            Self::ExpectedCloseTag(tag) => write!(f, "expected the close tag of '{}'", tag),
+            Self::ExpectedText => f.write_str("expected the value of the param"),
+            Self::UnhandledEvent(event) => write!(f, "unexpected {:?} event", event),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::QuickXml(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 // Bad practice to just copy event names?
 // I need to have an "expected" event type as well so I can't just use Event<'a>
 /// A bare enum recording possible XML events, named to mirror [Event]
@@ -342,7 +376,7 @@ impl<'a> ParamStack<'a> {
                     ($p:path) => {{
                         self.expect = Expect::Text;
                         $p(Default::default())
-                    }}
+                    }};
                 }
                 let p = match node_name {
                     b"bool" => default!(ParamKind::Bool),
@@ -447,7 +481,7 @@ impl<'a> ParamStack<'a> {
                     }
                     self.expect = Expect::CloseTag($tag_name);
                     Ok(())
-                }}
+                }};
             }
 
             match top {
@@ -466,6 +500,7 @@ impl<'a> ParamStack<'a> {
                 // The two cases below are designed to be impossible
                 ParamKind::List(_) => unreachable!(),
                 ParamKind::Struct(_) => unreachable!(),
+                ParamKind::Placeholder(_) => unreachable!(),
             }
         } else {
             Err(ReadError::from(&self.expect))
@@ -513,7 +548,7 @@ fn read_xml_loop<R: BufRead>(
         let event = try_with_position!(reader.read_event(buf));
         match event {
             Event::Start(start) => try_with_position!(stack.push(start.name(), start.attributes())),
-            Event::Text(text) => try_with_position!(stack.handle_text(&*text)),
+            Event::Text(text) => try_with_position!(stack.handle_text(&text)),
             Event::End(end) => {
                 if let Some(p) = try_with_position!(stack.pop(end.name())) {
                     return Ok(p);